@@ -9,4 +9,27 @@ pub mod modules {
     pub mod interval;
     pub mod camera;
     pub mod material;
+    pub mod texture;
+    pub mod normal_mapping;
+    pub mod onb;
+    pub mod microfacet;
+    pub mod conductor;
+    pub mod rough_dielectric;
+    pub mod principled;
+    pub mod layered;
+    pub mod thin_film;
+    pub mod spectral;
+    pub mod medium;
+    pub mod subsurface;
+    pub mod image_texture;
+    pub mod perlin;
+    pub mod worley;
+    pub mod quad;
+    pub mod alpha;
+    pub mod two_sided;
+    pub mod translucent;
+    pub mod light;
+    pub mod ies;
+    pub mod integrator;
+    pub mod aov;
 }
\ No newline at end of file
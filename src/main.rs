@@ -1,15 +1,19 @@
-use std::fs::File;
-use std::io::Write;
 use std::sync::Arc;
 
+use raytracer::modules::aov::Aov;
 use raytracer::modules::camera::Camera;
 use raytracer::modules::color::Color;
 use raytracer::modules::hittable_list::HittableList;
-use raytracer::modules::material::{Dielectric, Lambertian, Material, Metal};
+use raytracer::modules::hittable::Hittable;
+use raytracer::modules::light::{DirectionalLight, LightList};
+use raytracer::modules::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
 use raytracer::modules::sphere::Sphere;
+use raytracer::modules::texture::{CheckerTexture, NoiseTexture};
+use raytracer::modules::thin_film::ThinFilm;
 use raytracer::modules::utils::{random_double, random_double_range};
 use raytracer::modules::vec3::{random, random_in_range, Point3, Vec3};
 
+#[allow(dead_code)]
 fn use_default_world() -> HittableList {
     let mut world = HittableList::new();
 
@@ -17,7 +21,9 @@ fn use_default_world() -> HittableList {
     let material_ground = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
     let material_center = Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
     let material_left   = Arc::new(Dielectric::new(1.5));
-    let material_bubble   = Arc::new(Dielectric::new(1.0 / 1.5));
+    let material_bubble   = Arc::new(ThinFilm::on_dielectric(1.0, 1.33, 400.0)
+        .with_outer_ior(1.5)
+        .with_thickness_variation(Arc::new(NoiseTexture::new(4.0)), 250.0, 650.0));
     let material_right  = Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 1.0));
 
     // Add a few objects to our world
@@ -33,7 +39,8 @@ fn use_default_world() -> HittableList {
 fn generate_random_world() -> HittableList {
     let mut world = HittableList::new();
 
-    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let checker = Arc::new(CheckerTexture::from_colors(0.32, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9)));
+    let ground_material = Arc::new(Lambertian::from_texture(checker));
     let ground = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material);
     world.add(Arc::new(ground));
 
@@ -70,12 +77,64 @@ fn generate_random_world() -> HittableList {
     world
 }
 
+/*
+ * Night version of the random world: some of the small spheres are lamps, lit by the light tree
+ * (plus a faint moon).
+ */
+#[allow(dead_code)]
+fn generate_lit_random_world() -> (HittableList, LightList) {
+    let mut world = HittableList::new();
+    let mut lights = LightList::new();
+
+    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material)));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random_double();
+            let center = Point3::new((a as f64) + 0.9 * random_double(), 0.2, (b as f64) + 0.9 * random_double());
+
+            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                if choose_mat < 0.3 {
+                    // Lamp
+                    let lamp: Arc<dyn Hittable> = Arc::new(Sphere::new(center, 0.2, Arc::new(DiffuseLight::new(4.0 * random_in_range(0.3, 1.0)))));
+                    world.add(lamp.clone());
+                    lights.add_shape(lamp);
+                    continue;
+                }
+
+                let sphere_material: Arc<dyn Material> = if choose_mat < 0.8 {
+                    Arc::new(Lambertian::new(random()))
+                } else if choose_mat < 0.95 {
+                    Arc::new(Metal::new(random_in_range(0.5, 1.0), random_double_range(0.0, 0.5)))
+                } else {
+                    Arc::new(Dielectric::new(1.5))
+                };
+                world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+            }
+        }
+    }
+
+    let material1 = Arc::new(Dielectric::new(1.5));
+    world.add(Arc::new(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, material1)));
+    let material2 = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    world.add(Arc::new(Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, material2)));
+    let material3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    world.add(Arc::new(Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, material3)));
+
+    lights.add(Arc::new(DirectionalLight::new(Vec3::new(-1.0, -2.0, -1.0), Color::new(0.05, 0.06, 0.1)).with_angular_diameter(0.5)));
+
+    (world, lights)
+}
+
 fn main() {
-    // Create/open the output file
-    let mut image_file = File::create("output/image.ppm").expect("Failed to create file");
+    // AOV passes (normals, depth, ...) are only rendered with --aovs, and saved next to the image
+    let aovs: &[Aov] = if std::env::args().any(|arg| arg == "--aovs") { &Aov::ALL } else { &[] };
 
     // Create world
     let world = generate_random_world();
+    // Night version, render with its lights and a dark camera.background (e.g. Color::new(0.01, 0.01, 0.02))
+    // let (world, lights) = generate_lit_random_world();
 
     // Camera
     let mut camera = Camera::default();
@@ -108,7 +167,10 @@ fn main() {
     camera.defocus_angle = 0.6;
     camera.focus_dist = 10.0;
 
+    // Geometry preview without materials and lights (ambient occlusion within 1 unit, 4 rays per sample)
+    // camera.integrator = Arc::new(AmbientOcclusion::new(1.0).with_samples(4));
+
     // Render the world 
-    let image_string = camera.render(&world);
-    writeln!(image_file, "{}", image_string).expect("Failed to write world image");
+    let passes = camera.render_passes(&world, &LightList::new(), aovs);
+    passes.save("output/image.ppm").expect("Failed to write world image");
 }
\ No newline at end of file
@@ -0,0 +1,70 @@
+/*
+ * Alpha (opacity) cutouts
+ *
+ * Wraps a material with an opacity mask texture (red channel, 0 = fully transparent, 1 = fully opaque).
+ * Where the surface is cut out, hittables reject the hit and the ray continues as if nothing was there,
+ * so leaves, fences and decals can be modeled with single quads.
+ */
+
+use std::sync::Arc;
+
+use super::{color::Color, hittable::HitRecord, material::{BsdfSample, Material}, ray::Ray, texture::{IntoTexture, Texture}, utils::random_double, vec3::Vec3};
+
+#[derive(Clone, Copy)]
+pub enum AlphaMode {
+    // Opaque where the mask is at least the threshold, cut out elsewhere (crisp edges)
+    Threshold(f64),
+    // Opaque with probability equal to the mask (semi-transparent edges average out over samples)
+    Stochastic,
+}
+
+pub struct AlphaMasked {
+    base: Arc<dyn Material>,
+    mask: Arc<dyn Texture>,
+    mode: AlphaMode,
+}
+
+impl AlphaMasked {
+    pub fn new(base: Arc<dyn Material>, mask: impl IntoTexture, mode: AlphaMode) -> Self {
+        Self { base, mask: mask.into_texture(), mode }
+    }
+}
+
+impl Material for AlphaMasked {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        self.base.scatter(r_in, rec, attenuation, scattered)
+    }
+
+    fn is_opaque_at(&self, rec: &HitRecord) -> bool {
+        let alpha = self.mask.value_at(rec).x();
+        let opaque = match self.mode {
+            AlphaMode::Threshold(threshold) => alpha >= threshold,
+            AlphaMode::Stochastic => random_double() < alpha,
+        };
+        opaque && self.base.is_opaque_at(rec)
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        self.base.sample(r_in, rec)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.base.eval(r_in, rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        self.base.pdf(r_in, rec, direction)
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.base.emitted(rec)
+    }
+
+    fn average_emitted(&self) -> Color {
+        self.base.average_emitted()
+    }
+
+    fn albedo(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        self.base.albedo(r_in, rec)
+    }
+}
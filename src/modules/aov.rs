@@ -0,0 +1,261 @@
+/*
+ * AOVs (arbitrary output variables)
+ *
+ * Auxiliary images rendered alongside the beauty image, for compositing, denoising and debugging.
+ * They describe the first surface each camera ray hits, using the same jittered rays as the beauty pass,
+ * so their edges line up with it. Pixels where no sample hit anything are 0.
+ *
+ * Each pass is written as a PFM image (32-bit float RGB) holding the data as is, so depth and position keep
+ * their real values and can be used by a compositor. A PPM preview of each pass is made too, scaled into
+ * [0, 1] to look at, but too coarse (8 bits, normalized per image) to use as data.
+ *
+ * Normal: outward surface normal (before normal mapping), previewed as 0.5 * (n + 1)
+ * Depth: distance to the hit (t along a unit length ray), previewed as 1 at the nearest visible hit fading to
+ *        0 at the farthest
+ * Position: world position, previewed scaled to the bounds of the visible hits
+ * Albedo: `Material::albedo`, how much light the surface keeps (white for clear glass, black for lights)
+ * Material ID / Object ID: a flat color per material instance / per object in the world list
+ * UV: (u, v, 0) texture coordinates
+ * Hit count: number of surfaces the camera ray passes through, previewed scaled to the highest count in the image
+ */
+
+use std::{collections::HashMap, fs, io, path::Path, sync::Arc};
+
+use super::{color::{write_data_color, Color}, hittable::{HitRecord, Hittable}, interval::Interval, ray::Ray, utils::INFINITY, vec3::{Point3, Vec3}};
+
+// Most surfaces counted along a single ray by the hit count pass
+const MAX_HIT_COUNT: u32 = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Aov {
+    Normal,
+    Depth,
+    Position,
+    Albedo,
+    MaterialId,
+    ObjectId,
+    Uv,
+    HitCount,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 8] = [
+        Aov::Normal,
+        Aov::Depth,
+        Aov::Position,
+        Aov::Albedo,
+        Aov::MaterialId,
+        Aov::ObjectId,
+        Aov::Uv,
+        Aov::HitCount,
+    ];
+
+    // Short name of the pass, e.g. to name its image file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::Albedo => "albedo",
+            Aov::MaterialId => "material_id",
+            Aov::ObjectId => "object_id",
+            Aov::Uv => "uv",
+            Aov::HitCount => "hit_count",
+        }
+    }
+}
+
+// The images of a render: the beauty image as a PPM string and the requested AOVs, in the order requested
+pub struct RenderPasses {
+    pub beauty: String,
+    pub aovs: Vec<AovImage>,
+}
+
+impl RenderPasses {
+    /*
+     * Writes the beauty image to `path` and each AOV next to it, named after its pass:
+     * output/image.ppm gets output/image_depth.pfm with its preview output/image_depth.ppm, and so on.
+     */
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        fs::write(path, &self.beauty)?;
+
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("image");
+        for image in &self.aovs {
+            let aov_path = |extension: &str| path.with_file_name(format!("{}_{}.{}", stem, image.aov.name(), extension));
+            fs::write(aov_path("pfm"), &image.data)?;
+            fs::write(aov_path("ppm"), &image.preview)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct AovImage {
+    pub aov: Aov,
+    pub data: Vec<u8>, // PFM image with the values as they are
+    pub preview: String, // PPM image string, scaled to be viewable
+}
+
+// Sums of the AOV samples of one pixel
+#[derive(Clone, Default)]
+struct AovPixel {
+    samples: u32, // Camera rays through the pixel
+    hits: u32, // Camera rays that hit a surface
+    normal: Vec3,
+    depth: f64,
+    position: Point3,
+    albedo: Color,
+    uv: Vec3,
+    hit_count: f64,
+    material_id: Option<usize>, // IDs of the first hit, they can't be averaged
+    object_id: Option<usize>,
+}
+
+pub(crate) struct AovBuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<AovPixel>,
+    materials: HashMap<usize, usize>, // Material address -> ID, numbered in the order they're first seen
+}
+
+impl AovBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, pixels: vec![AovPixel::default(); (width * height) as usize], materials: HashMap::new() }
+    }
+
+    // Adds the first hit of a camera ray through pixel (i, j), skipping the costlier passes that aren't in `aovs`
+    pub fn add_sample(&mut self, i: u32, j: u32, r: &Ray, world: &dyn Hittable, aovs: &[Aov]) {
+        let pixel_index = (j * self.width + i) as usize;
+        self.pixels[pixel_index].samples += 1;
+
+        let mut rec = HitRecord::default();
+        if !world.hit(r, Interval::new(0.001, INFINITY), &mut rec) {
+            return;
+        }
+
+        let material_key = Arc::as_ptr(&rec.mat) as *const () as usize;
+        let next_id = self.materials.len();
+        let material_id = *self.materials.entry(material_key).or_insert(next_id);
+        let albedo = if aovs.contains(&Aov::Albedo) { rec.mat.albedo(r, &rec) } else { Color::zero() };
+        let hit_count = if aovs.contains(&Aov::HitCount) { hit_count(r, world) } else { 0 };
+
+        let pixel = &mut self.pixels[pixel_index];
+        pixel.hits += 1;
+        pixel.normal += if rec.front_face { rec.normal } else { -rec.normal };
+        pixel.depth += rec.t * r.direction().length();
+        pixel.position += rec.p;
+        pixel.albedo += albedo;
+        pixel.uv += Vec3::new(rec.u, rec.v, 0.0);
+        pixel.hit_count += hit_count as f64;
+        pixel.material_id.get_or_insert(material_id);
+        pixel.object_id.get_or_insert(rec.object_id);
+    }
+
+    pub fn to_image(&self, aov: Aov) -> AovImage {
+        AovImage { aov, data: self.to_pfm(aov), preview: self.to_ppm(aov) }
+    }
+
+    // Value of a pass at a pixel, unscaled
+    fn value(&self, pixel: &AovPixel, aov: Aov) -> Color {
+        if pixel.hits == 0 {
+            return Color::zero();
+        }
+
+        let hits = pixel.hits as f64;
+        match aov {
+            Aov::Normal => (pixel.normal / hits).unit_vector(),
+            Aov::Depth => {
+                let depth = pixel.depth / hits;
+                Color::new(depth, depth, depth)
+            }
+            Aov::Position => pixel.position / hits,
+            // Averaged over all samples, so edges blend into the background like in the beauty pass
+            Aov::Albedo => pixel.albedo / pixel.samples as f64,
+            Aov::MaterialId => pixel.material_id.map_or(Color::zero(), id_color),
+            Aov::ObjectId => pixel.object_id.map_or(Color::zero(), id_color),
+            Aov::Uv => pixel.uv / hits,
+            Aov::HitCount => {
+                let count = pixel.hit_count / pixel.samples as f64;
+                Color::new(count, count, count)
+            }
+        }
+    }
+
+    /*
+     * Writes one pass as a PFM image: a "PF" header with the size and a negative scale (little endian),
+     * then 32-bit float RGB pixels, bottom row first.
+     */
+    pub fn to_pfm(&self, aov: Aov) -> Vec<u8> {
+        let mut data = format!("PF\n{} {}\n-1.0\n", self.width, self.height).into_bytes();
+        for row in self.pixels.chunks(self.width as usize).rev() {
+            for pixel in row {
+                let value = self.value(pixel, aov);
+                for channel in [value.x(), value.y(), value.z()] {
+                    data.extend_from_slice(&(channel as f32).to_le_bytes());
+                }
+            }
+        }
+        data
+    }
+
+    // Writes one pass as a PPM image string, scaled into [0, 1] for viewing
+    pub fn to_ppm(&self, aov: Aov) -> String {
+        let mut image_string = format!("P3\n{} {}\n255\n", self.width, self.height);
+
+        // Ranges over the visible hits, to scale the passes that aren't already in [0, 1]
+        let hit_values = || self.pixels.iter().filter(|pixel| pixel.hits > 0).map(|pixel| self.value(pixel, aov));
+        let mut min = Color::new(INFINITY, INFINITY, INFINITY);
+        let mut max = -min;
+        for value in hit_values() {
+            min = Color::new(min.x().min(value.x()), min.y().min(value.y()), min.z().min(value.z()));
+            max = Color::new(max.x().max(value.x()), max.y().max(value.y()), max.z().max(value.z()));
+        }
+        let scale = |x: f64, min: f64, max: f64| if max > min { (x - min) / (max - min) } else { 0.5 };
+
+        for pixel in self.pixels.iter() {
+            let value = self.value(pixel, aov);
+            let preview = if pixel.hits == 0 {
+                Color::zero()
+            } else {
+                match aov {
+                    Aov::Normal => 0.5 * (value + Vec3::new(1.0, 1.0, 1.0)),
+                    Aov::Depth => {
+                        let depth = if max.x() > min.x() { (max.x() - value.x()) / (max.x() - min.x()) } else { 1.0 };
+                        Color::new(depth, depth, depth)
+                    }
+                    Aov::Position => Color::new(scale(value.x(), min.x(), max.x()), scale(value.y(), min.y(), max.y()), scale(value.z(), min.z(), max.z())),
+                    Aov::HitCount => {
+                        let count = if max.x() > 0.0 { value.x() / max.x() } else { 0.0 };
+                        Color::new(count, count, count)
+                    }
+                    Aov::Albedo | Aov::MaterialId | Aov::ObjectId | Aov::Uv => value,
+                }
+            };
+            image_string.push_str(&write_data_color(preview));
+        }
+
+        image_string
+    }
+}
+
+// Number of surfaces along the ray, found by hitting the world again just past each hit
+fn hit_count(r: &Ray, world: &dyn Hittable) -> u32 {
+    let mut rec = HitRecord::default();
+    let mut t_min = 0.001;
+    let mut count = 0;
+    while count < MAX_HIT_COUNT && world.hit(r, Interval::new(t_min, INFINITY), &mut rec) {
+        count += 1;
+        t_min = rec.t + 0.001;
+    }
+    count
+}
+
+// A distinct, not too dark color for an ID (scrambled so neighbouring IDs look different)
+fn id_color(id: usize) -> Color {
+    let mut h = (id as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    h ^= h >> 31;
+    h = h.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h ^= h >> 29;
+    let channel = |shift: u64| 0.2 + 0.8 * ((h >> shift) & 0xff) as f64 / 255.0;
+    Color::new(channel(0), channel(8), channel(16))
+}
@@ -1,6 +1,8 @@
 use crate::modules::color::write_color;
 
-use super::{color::Color, hittable::{HitRecord, Hittable}, hittable_list::HittableList, interval::Interval, ray::Ray, utils::{random_double, INFINITY}, vec3::{random_in_unit_disk, random_on_hemisphere, Point3, Vec3}};
+use std::sync::Arc;
+
+use super::{aov::{Aov, AovBuffer, RenderPasses}, color::Color, hittable_list::HittableList, integrator::{Integrator, PathTracer, RenderContext}, light::LightList, ray::{Ray, RayCone}, spectral::{spectrum_to_rgb, SampledWavelengths}, utils::random_double, vec3::{random_in_unit_disk, Point3, Vec3}};
 
 
 pub struct Camera {
@@ -8,6 +10,7 @@ pub struct Camera {
     pub image_width: u32, // Rendered image width in pixels
     pub samples_per_pixel: u32, // Number of samples per pixel
     pub max_depth: u32, // Maximum number of bounces a ray can make
+    pub russian_roulette_depth: u32, // Bounces before dim paths may be terminated early (Russian roulette)
 
     pub vfov: f64, // Vertical field of view in degrees
     pub lookfrom: Point3, // Camera position
@@ -15,6 +18,9 @@ pub struct Camera {
     pub vup: Vec3, // Camera up vector
     pub defocus_angle: f64, // Defocus angle in degrees 
     pub focus_dist: f64, // Distance from camera to perfect focus plane
+    pub spectral: bool, // Trace sampled wavelengths instead of RGB (needed for dispersion)
+    pub background: Option<Color>, // Color of rays that escape the scene, None for the sky gradient
+    pub integrator: Arc<dyn Integrator>, // Light transport algorithm computing each sample's color
 
     image_height: u32, // Rendered image height in pixels
     pixel_samples_scale: f64, // Color scale factor for a sum of pixel samples
@@ -31,13 +37,14 @@ pub struct Camera {
     defocus_disk_v: Vec3, // Defocus disk vertical vector
 }
 
-impl Camera {
-    pub fn default() -> Self {
+impl Default for Camera {
+    fn default() -> Self {
         Self {
             aspect_ratio: 1.0,
             image_width: 100,
             samples_per_pixel: 10,
             max_depth: 10,
+            russian_roulette_depth: 3,
 
             vfov: 90.0,
             lookfrom: Point3::new(0.0, 0.0, 0.0),
@@ -45,6 +52,9 @@ impl Camera {
             vup: Vec3::new(0.0, 1.0, 0.0),
             defocus_angle: 0.0,
             focus_dist: 10.0,
+            spectral: false,
+            background: None,
+            integrator: Arc::new(PathTracer),
 
             image_height: 0,
             pixel_samples_scale: 0.0,
@@ -60,10 +70,37 @@ impl Camera {
             defocus_disk_v: Vec3::zero(),
         }
     }
+}
+
+impl Camera {
+    /*
+     * Renders the world to a PPM image string.
+     *
+     * `lights` holds the emissive shapes (which must also be in the world) and analytic lights to sample directly
+     * at every diffuse bounce. An empty list falls back to finding lights only through scattered rays.
+     *
+     * Each sample's color comes from `integrator`.
+     */
+    pub fn render(&mut self, world: &HittableList, lights: &LightList) -> String {
+        self.render_passes(world, lights, &[]).beauty
+    }
 
-    pub fn render(&mut self, world: &HittableList) -> String {
+    /*
+     * Renders the world like `render`, along with the given AOV passes (normals, depth, ...), each as a float
+     * PFM image and a PPM preview. See the aov module for what each pass holds.
+     */
+    pub fn render_passes(&mut self, world: &HittableList, lights: &LightList, aovs: &[Aov]) -> RenderPasses {
         self.init();
         let mut image_string = String::new();
+        let mut aov_buffer = if aovs.is_empty() { None } else { Some(AovBuffer::new(self.image_width, self.image_height)) };
+
+        let context = RenderContext {
+            world,
+            lights,
+            background: self.background,
+            max_depth: self.max_depth,
+            russian_roulette_depth: self.russian_roulette_depth,
+        };
 
         // Write PPM header
         image_string.push_str(&format!("P3\n{} {}\n255\n", self.image_width, self.image_height));
@@ -75,8 +112,17 @@ impl Camera {
             for i in 0..self.image_width {
                 let mut pixel_color = Color::zero();
                 for _ in 0..self.samples_per_pixel {
-                    let r = self.get_ray(i, j);
-                    pixel_color += self.ray_color(&r, self.max_depth, world);
+                    let mut r = self.get_ray(i, j);
+                    if let Some(aov_buffer) = aov_buffer.as_mut() {
+                        aov_buffer.add_sample(i, j, &r, world, aovs);
+                    }
+                    if self.spectral {
+                        let wavelengths = SampledWavelengths::sample_uniform(random_double());
+                        r.set_wavelengths(Some(wavelengths));
+                        pixel_color += spectrum_to_rgb(self.integrator.li(r, &context), &wavelengths);
+                    } else {
+                        pixel_color += self.integrator.li(r, &context);
+                    }
                 }
 
                 // Write color to the image string output with newline
@@ -86,7 +132,12 @@ impl Camera {
         }
         eprintln!("Done.\n");
 
-        return image_string;
+        let aovs = match aov_buffer {
+            Some(aov_buffer) => aovs.iter().map(|&aov| aov_buffer.to_image(aov)).collect(),
+            None => Vec::new(),
+        };
+
+        RenderPasses { beauty: image_string, aovs }
     }
 
     fn init(&mut self) {
@@ -126,41 +177,6 @@ impl Camera {
         self.defocus_disk_v = self.v * defocus_radius;
     }
 
-    /* 
-     * Returns the color for a given scene ray
-     * 
-     * Linear gradient (linear interpolation); "lerp" between two values: (1 - a) * start + a * end
-     * where a: 0 -> 1
-     * 
-     * Color diffusion:
-     * If a ray bounces off a material and keeps 100% of its color, then it's white.
-     * If a ray bounces off a material and keeps 0% of its color, then it's black.
-     */
-    fn ray_color<T: Hittable>(&self, r: &Ray, depth: u32, world: &T) -> Color {
-        // No more light gathered if max ray bounce depth is reached
-        if depth <= 0 {
-            return Color::zero();
-        }
-
-        let mut rec = HitRecord::default();
-        // A ray attemps to accurately calculate the intersection point when intersecting with a hittable
-        // Someones this calculation is not accurate (floating point rounding error) so we add a small epsilon
-        // This fixes the "shadow acne" problem
-        if world.hit(r, Interval::new(0.001, INFINITY), &mut rec) {
-            let mut scattered = Ray::default();
-            let mut attenuation = Color::zero();
-            if rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
-                return attenuation * self.ray_color(&scattered, depth - 1, world);
-            }
-            return Color::zero();
-        }
-        
-        // If no hit, keep the sky gradient
-        let unit_direction = r.direction().unit_vector();
-        let a = 0.5 * (unit_direction.y() + 1.0);
-        (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
-    }
-
     /*
      * Constructs a camera ray originating from the camera defocus disk and passes through the pixel at (i, j)
      * Randomly samples the pixel to account for antialiasing
@@ -175,14 +191,18 @@ impl Camera {
 
         let ray_origin = if self.defocus_angle <= 0.0 { self.center } else { self.defocus_disk_sample() };
         let ray_direction = pixel_center - ray_origin;
-        Ray::new(ray_origin, ray_direction)
+        let mut ray = Ray::new(ray_origin, ray_direction);
+
+        // The pixel's footprint grows by one pixel width per focus distance (defocus blur isn't accounted for)
+        ray.set_cone(Some(RayCone { width: 0.0, spread: self.pixel_delta_u.length() / ray_direction.length() }));
+        ray
     }
 
     /*
      * Returns a random point in the [-.5,-.5]-[+.5,+.5] unit square centered on the pixel
      */
     fn pixel_sample_square(&self) -> Vec3 {
-        return Vec3::new(-0.5 + random_double(), -0.5 + random_double(), 0.0);
+        Vec3::new(-0.5 + random_double(), -0.5 + random_double(), 0.0)
     }
 
     // Returns a random point in the defocus disk
@@ -1,7 +1,5 @@
 use crate::modules::vec3::Vec3;
 
-use super::utils::random_double;
-
 pub type Color = Vec3;
 
 /*
@@ -24,9 +22,23 @@ pub fn write_color(pixel_color: Color) -> String {
     let g = linear_to_gamma(g);
     let b = linear_to_gamma(b);
 
+    write_data_color(Color::new(r, g, b))
+}
+
+// Writes values in [0, 1] as they are, for images holding data rather than colors (e.g. normals)
+pub fn write_data_color(pixel_color: Color) -> String {
+    let r = pixel_color.x();
+    let g = pixel_color.y();
+    let b = pixel_color.z();
+
     // Translate [0, 1] range to [0, 255] range, clamped
     format!("{} {} {}\n", 
         (256.0 * r.clamp(0.0, 0.999)) as u32, 
         (256.0 * g.clamp(0.0, 0.999)) as u32, 
         (256.0 * b.clamp(0.0, 0.999)) as u32)
 }
+
+// Perceived brightness of a linear color (Rec. 709 weights)
+pub fn luminance(color: Color) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
@@ -0,0 +1,179 @@
+/*
+ * Conductor (physically based metal)
+ *
+ * Rough metal using a GGX microfacet BRDF with Smith masking-shadowing. Reflected directions are picked by
+ * sampling the microfacet normals visible from the incoming direction and mirroring about them, so the ray
+ * weight is just F * G2 / G1 (no fake fuzz sphere, and grazing angles behave).
+ *
+ * The color comes from the complex index of refraction (eta + i k) per RGB channel, which gives the
+ * correct Fresnel tint shift towards white at grazing angles.
+ *
+ * Roughness can be anisotropic (brushed steel, vinyl records): it's set separately along the surface tangent
+ * and bitangent. The tangent comes from the hit record's shading frame (direction of increasing u), or from a
+ * tangent map giving the brushing direction per point.
+ */
+
+use std::sync::Arc;
+
+use super::{color::Color, hittable::HitRecord, material::{scatter_from_sample, BsdfSample, Material}, microfacet::{fresnel_conductor, TrowbridgeReitz}, onb::Onb, ray::Ray, texture::{IntoTexture, Texture}, utils::random_double, vec3::{reflect, Vec3}};
+
+pub struct Conductor {
+    eta: Color, // Real part of the index of refraction per channel
+    k: Color, // Imaginary part (absorption) of the index of refraction per channel
+    distribution: TrowbridgeReitz,
+    tangent_map: Option<Arc<dyn Texture>>, // Brushing direction in tangent space, encoded like a normal map
+}
+
+impl Conductor {
+    /*
+     * roughness: 0 is a perfect mirror, 1 is very rough
+     */
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Self {
+        Self::anisotropic(eta, k, roughness, roughness)
+    }
+
+    /*
+     * Separate roughness along the tangent (roughness_u) and the bitangent (roughness_v).
+     * Brushed metal is smooth along the brushing direction and rough across it.
+     */
+    pub fn anisotropic(eta: Color, k: Color, roughness_u: f64, roughness_v: f64) -> Self {
+        let alpha_x = TrowbridgeReitz::roughness_to_alpha(roughness_u.clamp(0.0, 1.0));
+        let alpha_y = TrowbridgeReitz::roughness_to_alpha(roughness_v.clamp(0.0, 1.0));
+        Self { eta, k, distribution: TrowbridgeReitz::new(alpha_x, alpha_y), tangent_map: None }
+    }
+
+    /*
+     * Per point tangent direction: the red and green channels map from [0, 1] to [-1, 1] along the surface
+     * tangent and bitangent (so (1, 0.5) is the default tangent and (0.5, 1) rotates it by 90 degrees).
+     * Like normal maps, load images of tangent maps with `ImageTexture::load_linear`.
+     */
+    pub fn with_tangent_map(mut self, tangent_map: impl IntoTexture) -> Self {
+        self.tangent_map = Some(tangent_map.into_texture());
+        self
+    }
+
+    // Keeps the optical constants (e.g. from a preset) and replaces the roughness with an anisotropic one
+    pub fn with_anisotropic_roughness(self, roughness_u: f64, roughness_v: f64) -> Self {
+        let tangent_map = self.tangent_map;
+        Self { tangent_map, ..Self::anisotropic(self.eta, self.k, roughness_u, roughness_v) }
+    }
+
+    /*
+     * Outgoing, incoming and half vector directions in the local shading frame, or None if the pair can't
+     * reflect off the surface (either side below it, a smooth mirror, or a degenerate half vector).
+     */
+    fn local_directions(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Option<(Vec3, Vec3, Vec3)> {
+        if self.distribution.effectively_smooth() {
+            return None;
+        }
+
+        let frame = Onb::from_normal_tangent(rec.normal, self.tangent(rec));
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        let wi = frame.to_local(direction.unit_vector());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return None;
+        }
+
+        let wm = wo + wi;
+        if wm.near_zero() {
+            return None;
+        }
+        Some((wo, wi, wm.unit_vector()))
+    }
+
+    fn tangent(&self, rec: &HitRecord) -> Vec3 {
+        match &self.tangent_map {
+            Some(map) => {
+                let c = map.value_at(rec);
+                (2.0 * c.x() - 1.0) * rec.tangent + (2.0 * c.y() - 1.0) * rec.bitangent
+            }
+            None => rec.tangent,
+        }
+    }
+
+    // Measured optical constants, sampled at the R, G and B wavelengths
+    pub fn gold(roughness: f64) -> Self {
+        Self::new(Color::new(0.143119, 0.374957, 1.44248), Color::new(3.98316, 2.38572, 1.60322), roughness)
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::new(Color::new(0.200438, 0.924033, 1.10221), Color::new(3.91295, 2.45285, 2.14219), roughness)
+    }
+
+    pub fn aluminum(roughness: f64) -> Self {
+        Self::new(Color::new(1.65746, 0.880369, 0.521229), Color::new(9.22387, 6.26952, 4.837), roughness)
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Self::new(Color::new(0.155265, 0.116723, 0.138342), Color::new(4.82835, 3.12225, 2.14696), roughness)
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        scatter_from_sample(self.sample(r_in, rec), attenuation, scattered)
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let unit_direction = r_in.direction().unit_vector();
+
+        // Smooth enough to be a mirror, skip the microfacet sampling
+        if self.distribution.effectively_smooth() {
+            let cos_theta = f64::min(-unit_direction.dot(&rec.normal), 1.0);
+            return Some(BsdfSample {
+                scattered: Ray::new(rec.p, reflect(unit_direction, rec.normal)),
+                weight: fresnel_conductor(cos_theta, self.eta, self.k),
+                pdf: 0.0,
+                is_specular: true,
+            });
+        }
+
+        // Work in the local shading frame, where the normal is +z and x follows the tangent
+        let frame = Onb::from_normal_tangent(rec.normal, self.tangent(rec));
+        let wo = frame.to_local(-unit_direction);
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let wm = self.distribution.sample_wm(wo, random_double(), random_double());
+        let wi = reflect(-wo, wm);
+        if wi.z() <= 0.0 {
+            // Reflected into the surface, i.e. blocked by another microfacet
+            return None;
+        }
+
+        // The visible normal pdf cancels D and the Jacobian, leaving F * G2 / G1
+        let f = fresnel_conductor(wo.dot(&wm), self.eta, self.k);
+        Some(BsdfSample {
+            scattered: Ray::new(rec.p, frame.transform(wi)),
+            weight: f * (self.distribution.g(wo, wi) / self.distribution.g1(wo)),
+            pdf: self.distribution.d_visible(wo, wm) / (4.0 * wo.dot(&wm)),
+            is_specular: false,
+        })
+    }
+
+    // D * F * G2 / (4 cos theta_o), the microfacet BRDF times cos theta_i
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        let Some((wo, wi, wm)) = self.local_directions(r_in, rec, direction) else {
+            return Color::zero();
+        };
+
+        let f = fresnel_conductor(wo.dot(&wm), self.eta, self.k);
+        f * (self.distribution.d(wm) * self.distribution.g(wo, wi) / (4.0 * wo.z()))
+    }
+
+    // Visible normal density times the Jacobian of the reflection, 1 / (4 |wo . wm|)
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let Some((wo, _, wm)) = self.local_directions(r_in, rec, direction) else {
+            return 0.0;
+        };
+
+        self.distribution.d_visible(wo, wm) / (4.0 * wo.dot(&wm))
+    }
+
+    // Reflectance towards the viewer, leaving out the shadowing between microfacets
+    fn albedo(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        let cos_theta = f64::min(-r_in.direction().unit_vector().dot(&rec.normal), 1.0);
+        fresnel_conductor(cos_theta, self.eta, self.k)
+    }
+}
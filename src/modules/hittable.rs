@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::modules::vec3::Vec3;
 
-use super::{color::Color, interval::Interval, material::{Lambertian, Material}, ray::Ray, vec3::Point3};
+use super::{color::Color, interval::Interval, light::LightBounds, material::{Lambertian, Material}, ray::Ray, vec3::Point3};
 
 #[derive(Clone)]
 pub struct HitRecord {
@@ -10,30 +10,102 @@ pub struct HitRecord {
     pub normal: Vec3,
     pub mat: Arc<dyn Material>,
     pub t: f64,
+    pub u: f64, // Surface texture coordinate u in [0, 1]
+    pub v: f64, // Surface texture coordinate v in [0, 1]
+    pub tangent: Vec3, // Unit surface direction of increasing u
+    pub bitangent: Vec3, // Unit surface direction of increasing v
     pub front_face: bool,
+    pub object_id: usize, // Index of the hit object in the outermost HittableList (the world)
+    pub uv_footprint: f64, // Width of the pixel's footprint in (u, v) units, 0 if unknown
 }
 
-impl HitRecord {
-    pub fn default() -> Self {
+impl Default for HitRecord {
+    fn default() -> Self {
         let default_material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.4, 0.4, 0.4)));
         Self {
             p: Point3::new(0.0, 0.0, 0.0),
             normal: Vec3::new(0.0, 0.0, 0.0),
             mat: default_material,
             t: 0.0,
+            u: 0.0,
+            v: 0.0,
+            tangent: Vec3::new(1.0, 0.0, 0.0),
+            bitangent: Vec3::new(0.0, 1.0, 0.0),
             front_face: false,
+            object_id: 0,
+            uv_footprint: 0.0,
         }
     }
+}
 
+impl HitRecord {
     // Sets the hit record normal vector
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
         // The parameter `outward_normal` assume unit length
         self.front_face = r.direction().dot(&outward_normal) < 0.0;
         self.normal = if self.front_face { outward_normal } else { -outward_normal };
     }
+
+    /*
+     * Sets the tangent frame used by normal/bump mapping (and anything else that needs to know how the
+     * texture (u, v) directions lie on the surface).
+     *
+     * The tangent is projected onto the surface and normalized, and the bitangent completes the frame
+     * against the outward normal, so it stays the direction of increasing v regardless of which face was hit.
+     */
+    pub fn set_tangent_frame(&mut self, outward_normal: Vec3, tangent: Vec3) {
+        let mut t = tangent - tangent.dot(&outward_normal) * outward_normal;
+        if t.near_zero() {
+            // Degenerate tangent (e.g. at a pole), pick any direction on the surface
+            let a = if outward_normal.x().abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+            t = outward_normal.cross(a);
+        }
+        self.tangent = t.unit_vector();
+        self.bitangent = outward_normal.cross(self.tangent);
+    }
+
+    /*
+     * Sets how much of the texture the ray's pixel covers at the hit, from the ray cone's width there.
+     * `u_scale` is the world space distance between lines of constant u one unit apart (1 / |grad u|, which is
+     * |dp/du| when dp/du and dp/dv are perpendicular), and `v_scale` the same for v. The footprint is measured
+     * along the coordinate changing fastest (the smaller scale), so stretched textures blur rather than alias.
+     * Seen at an angle the footprint stretches by 1 / cos, which is used for both directions.
+     * Call after `set_face_normal`.
+     */
+    pub fn set_uv_footprint(&mut self, r: &Ray, u_scale: f64, v_scale: f64) {
+        let uv_scale = f64::min(u_scale, v_scale);
+        self.uv_footprint = match r.cone() {
+            Some(cone) if uv_scale > 0.0 => {
+                let direction = r.direction();
+                let cos_theta = direction.unit_vector().dot(&self.normal).abs().max(1e-4);
+                cone.width_at(self.t * direction.length()) / (cos_theta * uv_scale)
+            }
+            _ => 0.0,
+        };
+    }
 }
 
 // Note: Hittable is a trait that can be implemented by any object that can be hit by a ray
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, ray_t: Interval, hit_record: &mut HitRecord) -> bool;
+
+    /*
+     * Light sampling (next-event estimation)
+     *
+     * Shapes used as lights pick directions from a point towards themselves (`random`), and report the
+     * solid angle density of picking a given direction that way (`pdf_value`, 0 if the direction misses).
+     * Shapes that can't be sampled keep the defaults and are never picked.
+     */
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    fn random(&self, _origin: Point3) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    // Bounding sphere and emitted power when used as a light, None if unknown (sampled without the light tree)
+    fn light_bounds(&self) -> Option<LightBounds> {
+        None
+    }
 }
\ No newline at end of file
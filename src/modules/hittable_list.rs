@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use super::{hittable::{HitRecord, Hittable}, interval::Interval, ray::Ray};
+use super::{hittable::{HitRecord, Hittable}, interval::Interval, ray::Ray, utils::random_double, vec3::{Point3, Vec3}};
 
 /*
  * Box vs. Arc:
@@ -14,6 +14,12 @@ pub struct HittableList {
     objects: Vec<Arc<dyn Hittable>>,
 }
 
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HittableList {
     pub fn new() -> Self {
         Self { objects: Vec::new() }
@@ -26,6 +32,14 @@ impl HittableList {
     pub fn clear(&mut self) {
         self.objects.clear();
     }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
 }
 
 impl Hittable for HittableList {
@@ -34,14 +48,34 @@ impl Hittable for HittableList {
         let mut hit_anything = false;
         let mut closest_so_far = ray_t.max;
 
-        for object in self.objects.iter() {
+        for (index, object) in self.objects.iter().enumerate() {
             if object.hit(r, Interval::new(ray_t.min, closest_so_far), &mut temp_rec) {
                 hit_anything = true;
                 closest_so_far = temp_rec.t;
+                temp_rec.object_id = index;
                 *rec = temp_rec.clone();
             }
         }
 
-        return hit_anything;
+        hit_anything
+    }
+
+    // Picks one of the objects uniformly, so the density is the average of the objects' densities
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+
+        let weight = 1.0 / self.objects.len() as f64;
+        self.objects.iter().map(|object| weight * object.pdf_value(origin, direction)).sum()
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+
+        let index = ((random_double() * self.objects.len() as f64) as usize).min(self.objects.len() - 1);
+        self.objects[index].random(origin)
     }
 }
\ No newline at end of file
@@ -0,0 +1,384 @@
+/*
+ * IES photometric profiles (IESNA LM-63)
+ *
+ * Luminaire manufacturers publish how bright a fixture is in every direction as an IES file: a table of
+ * candela values over vertical angles (0 = straight down from the fixture, 180 = straight up) and horizontal
+ * angles around the vertical axis. Point and spot lights can use one so their light spreads like the real
+ * fixture (the "scallops" on a wall under a downlight).
+ *
+ * Only type C photometry is supported, which is what architectural luminaires use.
+ * The values are normalized so the brightest direction is 1 and the light's intensity sets the actual level.
+ */
+
+use std::{fs, io, path::Path};
+
+use super::{utils::PI, vec3::Vec3};
+
+pub struct IesProfile {
+    vertical_angles: Vec<f64>, // Degrees from the nadir, ascending
+    horizontal_angles: Vec<f64>, // Degrees around the vertical axis, ascending
+    candela: Vec<Vec<f64>>, // candela[h][v], normalized to a peak of 1
+    peak_candela: f64, // Brightest value in the file, in candela
+    distribution: DirectionDistribution,
+}
+
+// Resolution of the tabulated distribution used for sampling directions (in theta and phi)
+const THETA_BINS: usize = 64;
+const PHI_BINS: usize = 128;
+
+impl IesProfile {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /*
+     * Parses the contents of an IES file.
+     *
+     * Layout: "IESNA:LM-63-..." and [KEYWORD] header lines, a TILT= line (with tilt data following for
+     * TILT=INCLUDE, which is skipped), then whitespace separated numbers: the lamp and photometric data,
+     * the ballast line, the vertical and horizontal angles, and the candela values per horizontal angle.
+     */
+    pub fn parse(contents: &str) -> io::Result<Self> {
+        let mut lines = contents.lines();
+        let tilt = loop {
+            let line = lines.next().ok_or_else(|| invalid("missing TILT line"))?;
+            if let Some(tilt) = line.trim().strip_prefix("TILT=") {
+                break tilt.trim().to_string();
+            }
+        };
+
+        let rest: Vec<&str> = lines.collect();
+        let mut numbers = rest.iter().flat_map(|line| line.split_whitespace()).map(|token| {
+            token.parse::<f64>().map_err(|_| invalid(&format!("expected a number, found '{}'", token)))
+        });
+        let mut next = || numbers.next().unwrap_or_else(|| Err(invalid("unexpected end of file")));
+
+        if tilt == "INCLUDE" {
+            // Lamp-to-luminaire geometry, then tilt angles and their multipliers
+            next()?;
+            let tilt_count = next()? as usize;
+            for _ in 0..tilt_count.saturating_mul(2) {
+                next()?;
+            }
+        }
+
+        let _lamp_count = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()?;
+        let _units = next()?;
+        let (_width, _length, _height) = (next()?, next()?, next()?);
+        let ballast_factor = next()?;
+        let _ballast_lamp_factor = next()?;
+        let _input_watts = next()?;
+
+        if photometric_type != 1.0 {
+            return Err(invalid("only type C photometry is supported"));
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(invalid("no angles"));
+        }
+
+        let vertical_angles = (0..vertical_count).map(|_| next()).collect::<io::Result<Vec<f64>>>()?;
+        let horizontal_angles = (0..horizontal_count).map(|_| next()).collect::<io::Result<Vec<f64>>>()?;
+
+        // Not reserved up front: the counts come from the file, only values actually in it get stored
+        let mut candela = Vec::new();
+        for _ in 0..horizontal_count {
+            let row = (0..vertical_count).map(|_| next().map(|c| c * multiplier * ballast_factor)).collect::<io::Result<Vec<f64>>>()?;
+            candela.push(row);
+        }
+
+        Self::from_table(vertical_angles, horizontal_angles, candela)
+    }
+
+    /*
+     * Builds a profile from a candela table: candela[h][v] is the value at horizontal_angles[h] and
+     * vertical_angles[v] (degrees, both ascending).
+     */
+    pub fn from_table(vertical_angles: Vec<f64>, horizontal_angles: Vec<f64>, mut candela: Vec<Vec<f64>>) -> io::Result<Self> {
+        let ascending = |angles: &[f64]| angles.windows(2).all(|pair| pair[0] < pair[1]);
+        if !ascending(&vertical_angles) || !ascending(&horizontal_angles) {
+            return Err(invalid("angles must be ascending"));
+        }
+        if candela.len() != horizontal_angles.len() || candela.iter().any(|row| row.len() != vertical_angles.len()) {
+            return Err(invalid("candela table doesn't match the angles"));
+        }
+
+        let peak_candela = candela.iter().flatten().fold(0.0, |peak: f64, &c| peak.max(c));
+        if peak_candela <= 0.0 {
+            return Err(invalid("no light in any direction"));
+        }
+        for value in candela.iter_mut().flatten() {
+            *value = value.max(0.0) / peak_candela;
+        }
+
+        let mut profile = Self { vertical_angles, horizontal_angles, candela, peak_candela, distribution: DirectionDistribution::default() };
+        profile.distribution = DirectionDistribution::new(&profile);
+        Ok(profile)
+    }
+
+    pub fn peak_candela(&self) -> f64 {
+        self.peak_candela
+    }
+
+    /*
+     * Relative intensity (peak 1) towards a direction in the luminaire's frame: +z is the nadir
+     * (vertical angle 0) and the horizontal angle is measured from +x towards +y.
+     */
+    pub fn evaluate(&self, local: Vec3) -> f64 {
+        let local = local.unit_vector();
+        let theta = f64::acos(local.z().clamp(-1.0, 1.0)).to_degrees();
+        let phi = f64::atan2(local.y(), local.x()).to_degrees().rem_euclid(360.0);
+        self.evaluate_angles(theta, phi)
+    }
+
+    // Integral of the relative intensity over all directions (4 pi for a uniform profile)
+    pub fn integral(&self) -> f64 {
+        self.distribution.total
+    }
+
+    /*
+     * Samples a direction in the luminaire's frame proportionally to the intensity, from two uniform numbers
+     * in [0, 1). Returns the direction and its solid angle density.
+     */
+    pub fn sample_direction(&self, u1: f64, u2: f64) -> (Vec3, f64) {
+        self.distribution.sample(u1, u2)
+    }
+
+    pub fn pdf(&self, local: Vec3) -> f64 {
+        self.distribution.pdf(local)
+    }
+
+    fn evaluate_angles(&self, theta: f64, phi: f64) -> f64 {
+        let phi = self.fold_horizontal(phi);
+
+        let (h0, h1, th) = segment(&self.horizontal_angles, phi);
+        let (v0, v1, tv) = match segment_within(&self.vertical_angles, theta) {
+            Some(segment) => segment,
+            None => return 0.0, // No data beyond the measured vertical range means no light there
+        };
+
+        let at = |h: usize| (1.0 - tv) * self.candela[h][v0] + tv * self.candela[h][v1];
+        (1.0 - th) * at(h0) + th * at(h1)
+    }
+
+    /*
+     * The first and last horizontal angles tell the symmetry of the data: a single angle for rotationally
+     * symmetric, 0-90 for symmetric in each quadrant, 0-180 for symmetric about the 0-180 plane, 90-270 for
+     * symmetric about the 90-270 plane, 0-360 for no symmetry.
+     */
+    fn fold_horizontal(&self, phi: f64) -> f64 {
+        let first = self.horizontal_angles[0];
+        let last = self.horizontal_angles[self.horizontal_angles.len() - 1];
+        if self.horizontal_angles.len() == 1 {
+            first
+        } else if first >= 90.0 && last <= 270.0 {
+            if phi < 90.0 {
+                180.0 - phi
+            } else if phi > 270.0 {
+                540.0 - phi
+            } else {
+                phi
+            }
+        } else if last <= 90.0 {
+            let phi = if phi > 180.0 { 360.0 - phi } else { phi };
+            if phi > 90.0 { 180.0 - phi } else { phi }
+        } else if last <= 180.0 {
+            if phi > 180.0 { 360.0 - phi } else { phi }
+        } else {
+            phi
+        }
+    }
+}
+
+/*
+ * Piecewise constant distribution of the intensity over a (theta, phi) grid, weighted by each cell's solid angle.
+ * Within a cell directions are uniform in cos theta and phi, i.e. uniform over the cell's solid angle.
+ */
+#[derive(Default)]
+struct DirectionDistribution {
+    cells: Vec<f64>, // Cell probabilities, row (theta) major
+    theta_cdf: Vec<f64>, // Cumulative probability of the rows
+    phi_cdfs: Vec<Vec<f64>>, // Cumulative probability within each row
+    total: f64, // Integral of the intensity over the sphere
+}
+
+impl DirectionDistribution {
+    fn new(profile: &IesProfile) -> Self {
+        let d_theta = 180.0 / THETA_BINS as f64;
+        let d_phi = 360.0 / PHI_BINS as f64;
+
+        let mut weights = vec![0.0; THETA_BINS * PHI_BINS];
+        let mut total = 0.0;
+        for i in 0..THETA_BINS {
+            let solid_angle = cell_solid_angle(i);
+            for j in 0..PHI_BINS {
+                let intensity = profile.evaluate_angles((i as f64 + 0.5) * d_theta, (j as f64 + 0.5) * d_phi);
+                weights[i * PHI_BINS + j] = intensity * solid_angle;
+                total += intensity * solid_angle;
+            }
+        }
+        if total <= 0.0 {
+            return Self::default();
+        }
+
+        let cells: Vec<f64> = weights.iter().map(|w| w / total).collect();
+        let mut theta_cdf = Vec::with_capacity(THETA_BINS);
+        let mut phi_cdfs = Vec::with_capacity(THETA_BINS);
+        let mut row_sum = 0.0;
+        for row in cells.chunks(PHI_BINS) {
+            let row_total: f64 = row.iter().sum();
+            row_sum += row_total;
+            theta_cdf.push(row_sum);
+
+            let mut acc = 0.0;
+            phi_cdfs.push(row.iter().map(|p| {
+                acc += if row_total > 0.0 { p / row_total } else { 1.0 / PHI_BINS as f64 };
+                acc
+            }).collect());
+        }
+
+        Self { cells, theta_cdf, phi_cdfs, total }
+    }
+
+    fn sample(&self, u1: f64, u2: f64) -> (Vec3, f64) {
+        if self.cells.is_empty() {
+            return (Vec3::new(0.0, 0.0, 1.0), 0.0);
+        }
+
+        let (i, u1) = pick(&self.theta_cdf, u1);
+        let (j, u2) = pick(&self.phi_cdfs[i], u2);
+
+        // Uniform over the cell's solid angle
+        let cos_0 = f64::cos(i as f64 * PI / THETA_BINS as f64);
+        let cos_1 = f64::cos((i + 1) as f64 * PI / THETA_BINS as f64);
+        let cos_theta = cos_0 + u1 * (cos_1 - cos_0);
+        let sin_theta = f64::sqrt((1.0 - cos_theta * cos_theta).max(0.0));
+        let phi = (j as f64 + u2) * 2.0 * PI / PHI_BINS as f64;
+
+        let direction = Vec3::new(sin_theta * f64::cos(phi), sin_theta * f64::sin(phi), cos_theta);
+        (direction, self.cells[i * PHI_BINS + j] / cell_solid_angle(i))
+    }
+
+    fn pdf(&self, local: Vec3) -> f64 {
+        if self.cells.is_empty() {
+            return 0.0;
+        }
+
+        let local = local.unit_vector();
+        let theta = f64::acos(local.z().clamp(-1.0, 1.0));
+        let phi = f64::atan2(local.y(), local.x()).rem_euclid(2.0 * PI);
+        let i = ((theta / PI * THETA_BINS as f64) as usize).min(THETA_BINS - 1);
+        let j = ((phi / (2.0 * PI) * PHI_BINS as f64) as usize).min(PHI_BINS - 1);
+        self.cells[i * PHI_BINS + j] / cell_solid_angle(i)
+    }
+}
+
+fn cell_solid_angle(theta_bin: usize) -> f64 {
+    let cos_0 = f64::cos(theta_bin as f64 * PI / THETA_BINS as f64);
+    let cos_1 = f64::cos((theta_bin + 1) as f64 * PI / THETA_BINS as f64);
+    (cos_0 - cos_1) * 2.0 * PI / PHI_BINS as f64
+}
+
+// Index of the first cdf entry above u, and u remapped to [0, 1) within that entry
+fn pick(cdf: &[f64], u: f64) -> (usize, f64) {
+    let u = u * cdf.last().copied().unwrap_or(1.0);
+    let index = cdf.partition_point(|&c| c <= u).min(cdf.len() - 1);
+    let start = if index == 0 { 0.0 } else { cdf[index - 1] };
+    let width = cdf[index] - start;
+    let remapped = if width > 0.0 { (u - start) / width } else { 0.5 };
+    (index, remapped.clamp(0.0, 1.0 - f64::EPSILON))
+}
+
+// Neighboring angles around x and the interpolation weight, clamped to the ends
+fn segment(angles: &[f64], x: f64) -> (usize, usize, f64) {
+    segment_within(angles, x.clamp(angles[0], angles[angles.len() - 1])).unwrap_or((0, 0, 0.0))
+}
+
+// Like segment, but None when x is outside the angles' range
+fn segment_within(angles: &[f64], x: f64) -> Option<(usize, usize, f64)> {
+    let last = angles.len() - 1;
+    if x < angles[0] || x > angles[last] {
+        return None;
+    }
+    if last == 0 {
+        return Some((0, 0, 0.0));
+    }
+
+    let i = angles.partition_point(|&a| a <= x).clamp(1, last);
+    let t = (x - angles[i - 1]) / (angles[i] - angles[i - 1]);
+    Some((i - 1, i, t))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid IES file: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Header and photometric data for a table with the given numbers of vertical and horizontal angles
+    fn ies_file(vertical_count: usize, horizontal_count: usize, table: &str) -> String {
+        format!(
+            "IESNA:LM-63-2002\n[TEST] fixture\nTILT=NONE\n1 -1 1 {} {} 1 1 0 0 0\n1 1 0\n{}\n",
+            vertical_count, horizontal_count, table
+        )
+    }
+
+    // Direction at the given vertical and horizontal angles (degrees) in the luminaire's frame
+    fn direction(theta: f64, phi: f64) -> Vec3 {
+        let (theta, phi) = (theta.to_radians(), phi.to_radians());
+        Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos())
+    }
+
+    #[test]
+    fn parses_and_normalizes_candela() {
+        let profile = IesProfile::parse(&ies_file(3, 1, "0 90 180\n0\n500 250 0")).unwrap();
+        assert_eq!(profile.peak_candela(), 500.0);
+        assert!((profile.evaluate(direction(0.0, 0.0)) - 1.0).abs() < 1e-9);
+        assert!((profile.evaluate(direction(45.0, 123.0)) - 0.75).abs() < 1e-9);
+        assert!(profile.evaluate(direction(180.0, 0.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mirrors_lateral_symmetry_about_the_90_270_plane() {
+        let profile = IesProfile::parse(&ies_file(2, 3, "0 90\n90 180 270\n300 300\n200 200\n300 300")).unwrap();
+        assert!((profile.evaluate(direction(45.0, 0.0)) - 2.0 / 3.0).abs() < 1e-9);
+        assert!((profile.evaluate(direction(45.0, 315.0)) - 5.0 / 6.0).abs() < 1e-9);
+        assert!((profile.evaluate(direction(45.0, 45.0)) - profile.evaluate(direction(45.0, 135.0))).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mirrors_quadrant_symmetry() {
+        let profile = IesProfile::parse(&ies_file(2, 2, "0 90\n0 90\n100 100\n50 50")).unwrap();
+        let expected = profile.evaluate(direction(30.0, 60.0));
+        for phi in [120.0, 240.0, 300.0] {
+            assert!((profile.evaluate(direction(30.0, phi)) - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn uniform_profile_integrates_to_the_sphere() {
+        let profile = IesProfile::parse(&ies_file(2, 1, "0 180\n0\n100 100")).unwrap();
+        assert!((profile.integral() - 4.0 * PI).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_counts_beyond_the_end_of_the_file() {
+        let file = ies_file(2, 1, "0 180\n0\n100 100").replace("1 -1 1 2 1 1 1", "1 -1 1 2 1000000000000000000 1 1");
+        assert!(IesProfile::parse(&file).is_err());
+
+        let tilted = ies_file(2, 1, "0 180\n0\n100 100").replace("TILT=NONE", "TILT=INCLUDE\n1\n1e30");
+        assert!(IesProfile::parse(&tilted).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_photometry() {
+        let file = ies_file(2, 1, "0 180\n0\n100 100").replace("1 -1 1 2 1 1 1", "1 -1 1 2 1 3 1");
+        assert!(IesProfile::parse(&file).is_err());
+    }
+}
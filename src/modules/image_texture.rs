@@ -0,0 +1,243 @@
+/*
+ * Image Texture
+ *
+ * Maps an image (PNG, JPEG or Radiance HDR) onto a surface using its (u, v) coordinates.
+ * (0, 0) is the bottom-left corner of the image and (1, 1) the top-right.
+ *
+ * Pixels are stored as linear colors: 8/16-bit images are assumed to be sRGB encoded and are linearized
+ * on load (unless loaded with `load_linear`, e.g. for data like normal maps), HDR images are already linear.
+ *
+ * Filtering:
+ * - Nearest: the texel the (u, v) lands in
+ * - Bilinear: blend of the 4 closest texels
+ * - Trilinear: blend of bilinear lookups in the two closest mipmap levels
+ *
+ * Trilinear filtering picks the mipmap level from the hit's footprint (`HitRecord::uv_footprint`, from the
+ * camera's ray cones): the level where one texel is about as wide as the pixel's footprint, so far away and
+ * grazing surfaces don't alias. Lookups without a footprint (e.g. through `value`) use the full resolution.
+ * `with_lod_bias` shifts the level, towards blurrier (> 0) or sharper (< 0).
+ */
+
+use std::path::Path;
+
+use image::{DynamicImage, ImageResult};
+
+use super::{color::Color, hittable::HitRecord, texture::Texture, vec3::Point3};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    Nearest,
+    Bilinear,
+    Trilinear,
+}
+
+// How texel lookups outside of [0, 1] are handled
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat, // Tile the image
+    Clamp, // Stretch the edge texels
+    Mirror, // Tile the image, flipping every other copy
+}
+
+// A single level of the mipmap pyramid, stored top row first
+struct MipLevel {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl MipLevel {
+    // Half resolution copy of this level using a 2x2 box filter
+    fn downsample(&self) -> MipLevel {
+        let width = usize::max(self.width / 2, 1);
+        let height = usize::max(self.height / 2, 1);
+        let mut pixels = Vec::with_capacity(width * height);
+
+        for j in 0..height {
+            for i in 0..width {
+                // Clamp so odd sized (or 1 pixel wide) levels don't read out of bounds
+                let x0 = usize::min(2 * i, self.width - 1);
+                let x1 = usize::min(2 * i + 1, self.width - 1);
+                let y0 = usize::min(2 * j, self.height - 1);
+                let y1 = usize::min(2 * j + 1, self.height - 1);
+
+                let sum = self.pixels[y0 * self.width + x0] + self.pixels[y0 * self.width + x1]
+                    + self.pixels[y1 * self.width + x0] + self.pixels[y1 * self.width + x1];
+                pixels.push(0.25 * sum);
+            }
+        }
+
+        MipLevel { width, height, pixels }
+    }
+
+    fn texel(&self, i: i64, j: i64, wrap: WrapMode) -> Color {
+        let x = wrap_index(i, self.width, wrap);
+        let y = wrap_index(j, self.height, wrap);
+        self.pixels[y * self.width + x]
+    }
+
+    fn sample_nearest(&self, u: f64, v: f64, wrap: WrapMode) -> Color {
+        // Flip v so that v = 0 is the bottom row of the image
+        let i = (u * self.width as f64).floor() as i64;
+        let j = ((1.0 - v) * self.height as f64).floor() as i64;
+        self.texel(i, j, wrap)
+    }
+
+    fn sample_bilinear(&self, u: f64, v: f64, wrap: WrapMode) -> Color {
+        // Texel centers sit at half-integer coordinates
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let i = x.floor();
+        let j = y.floor();
+        let fx = x - i;
+        let fy = y - j;
+        let (i, j) = (i as i64, j as i64);
+
+        (1.0 - fx) * (1.0 - fy) * self.texel(i, j, wrap)
+            + fx * (1.0 - fy) * self.texel(i + 1, j, wrap)
+            + (1.0 - fx) * fy * self.texel(i, j + 1, wrap)
+            + fx * fy * self.texel(i + 1, j + 1, wrap)
+    }
+}
+
+pub struct ImageTexture {
+    levels: Vec<MipLevel>, // Mipmap pyramid, levels[0] is the full resolution image
+    filter: FilterMode,
+    wrap: WrapMode,
+    lod_bias: f64, // Added to the mipmap level of detail picked by trilinear filtering
+}
+
+impl ImageTexture {
+    /*
+     * Loads an image file, linearizing sRGB encoded (8/16-bit) images.
+     */
+    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        let image = image::open(path)?;
+        let srgb = !matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+        Ok(Self::from_image(image, srgb))
+    }
+
+    /*
+     * Loads an image file as-is, for images that store data rather than colors (e.g. normal maps).
+     */
+    pub fn load_linear<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        Ok(Self::from_image(image::open(path)?, false))
+    }
+
+    /*
+     * Builds a texture from linear colors, stored row by row starting with the top row.
+     */
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0 && pixels.len() == width * height, "pixel count must match width * height");
+
+        // Build the mipmap chain all the way down to a single texel
+        let mut levels = vec![MipLevel { width, height, pixels }];
+        loop {
+            let last = levels.last().unwrap();
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = last.downsample();
+            levels.push(next);
+        }
+
+        Self { levels, filter: FilterMode::Bilinear, wrap: WrapMode::Repeat, lod_bias: 0.0 }
+    }
+
+    fn from_image(image: DynamicImage, srgb: bool) -> Self {
+        let image = image.into_rgb32f();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let decode = |c: f32| if srgb { srgb_to_linear(c as f64) } else { c as f64 };
+
+        let pixels = image.pixels().map(|p| Color::new(decode(p[0]), decode(p[1]), decode(p[2]))).collect();
+        Self::from_pixels(width, height, pixels)
+    }
+
+    pub fn with_filter(mut self, filter: FilterMode) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    // Offset to the level of detail picked by trilinear filtering, in levels (each +1 halves the resolution)
+    pub fn with_lod_bias(mut self, lod_bias: f64) -> Self {
+        self.lod_bias = lod_bias;
+        self
+    }
+
+    pub fn width(&self) -> usize { self.levels[0].width }
+    pub fn height(&self) -> usize { self.levels[0].height }
+
+    /*
+     * Samples the mipmap pyramid at a fractional level of detail, blending the two closest levels.
+     */
+    pub fn sample_lod(&self, u: f64, v: f64, lod: f64) -> Color {
+        let max_level = (self.levels.len() - 1) as f64;
+        let lod = lod.clamp(0.0, max_level);
+        let lower = lod.floor() as usize;
+        let upper = usize::min(lower + 1, self.levels.len() - 1);
+        let t = lod - lower as f64;
+
+        let a = self.levels[lower].sample_bilinear(u, v, self.wrap);
+        if t == 0.0 || lower == upper {
+            return a;
+        }
+        let b = self.levels[upper].sample_bilinear(u, v, self.wrap);
+        (1.0 - t) * a + t * b
+    }
+
+    /*
+     * Level of detail where a texel is as wide as a footprint of the given width in (u, v) units.
+     * Non-square images use the geometric mean of their width and height.
+     */
+    fn footprint_lod(&self, uv_footprint: f64) -> f64 {
+        if uv_footprint <= 0.0 {
+            return self.lod_bias;
+        }
+        let resolution = f64::sqrt((self.width() * self.height()) as f64);
+        f64::log2(uv_footprint * resolution) + self.lod_bias
+    }
+
+    fn filtered(&self, u: f64, v: f64, uv_footprint: f64) -> Color {
+        match self.filter {
+            FilterMode::Nearest => self.levels[0].sample_nearest(u, v, self.wrap),
+            FilterMode::Bilinear => self.levels[0].sample_bilinear(u, v, self.wrap),
+            FilterMode::Trilinear => self.sample_lod(u, v, self.footprint_lod(uv_footprint)),
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        self.filtered(u, v, 0.0)
+    }
+
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.filtered(rec.u, rec.v, rec.uv_footprint)
+    }
+}
+
+// Maps a (possibly out of range) texel index into [0, n)
+fn wrap_index(i: i64, n: usize, wrap: WrapMode) -> usize {
+    let n = n as i64;
+    let i = match wrap {
+        WrapMode::Repeat => i.rem_euclid(n),
+        WrapMode::Clamp => i.clamp(0, n - 1),
+        WrapMode::Mirror => {
+            let m = i.rem_euclid(2 * n);
+            if m < n { m } else { 2 * n - 1 - m }
+        }
+    };
+    i as usize
+}
+
+/*
+ * sRGB transfer function (decode): the piecewise linear/power curve used to encode most 8-bit images.
+ */
+pub fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
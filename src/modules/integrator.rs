@@ -0,0 +1,277 @@
+/*
+ * Integrators
+ *
+ * An integrator computes the light arriving at the camera along a ray: the light transport algorithm.
+ * The camera only generates rays and averages what the integrator returns, so different algorithms
+ * (full path tracing, simpler or debugging ones) can be swapped in with `Camera::integrator`.
+ *
+ * In spectral mode the returned "color" holds one value per sampled wavelength of the ray,
+ * and every RGB quantity along the path is converted with `to_path_space`.
+ */
+
+use super::{color::Color, hittable::{HitRecord, Hittable}, interval::Interval, light::{LightList, SampledLight}, medium::MediumEvent, onb::Onb, ray::{Ray, RayCone}, spectral::{to_path_space, SampledWavelengths}, utils::{power_heuristic, random_double, INFINITY}, vec3::random_cosine_direction};
+
+pub trait Integrator: Send + Sync {
+    fn li(&self, r: Ray, context: &RenderContext) -> Color;
+}
+
+// What an integrator gets to work with, set up by the camera for each render
+pub struct RenderContext<'a> {
+    pub world: &'a dyn Hittable,
+    pub lights: &'a LightList,
+    pub background: Option<Color>, // Color of rays that escape the scene, None for the sky gradient
+    pub max_depth: u32, // Maximum number of bounces a ray can make
+    pub russian_roulette_depth: u32, // Bounces before dim paths may be terminated early
+}
+
+impl RenderContext<'_> {
+    /*
+     * Color of a ray leaving the scene: the solid background color if set, otherwise a sky gradient.
+     *
+     * Linear gradient (linear interpolation); "lerp" between two values: (1 - a) * start + a * end
+     * where a: 0 -> 1
+     */
+    pub fn background(&self, r: &Ray) -> Color {
+        match self.background {
+            Some(color) => color,
+            None => {
+                let unit_direction = r.direction().unit_vector();
+                let a = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
+            }
+        }
+    }
+}
+
+/*
+ * Path tracing with next-event estimation (the default).
+ *
+ * At every bounce a shadow ray is sent towards a random point on one of the lights, and the scattered ray may
+ * hit a light too. Both estimate the same light, so they're combined with multiple importance sampling (power
+ * heuristic): each is weighted by how likely its technique was to pick that direction compared to the other.
+ * Shadow rays only see the non-specular part of the BSDF (`eval` is zero for delta lobes), so light found by
+ * specular bounces counts in full.
+ */
+pub struct PathTracer;
+
+impl Integrator for PathTracer {
+    fn li(&self, r: Ray, context: &RenderContext) -> Color {
+        trace_path(r, context, true)
+    }
+}
+
+/*
+ * Plain path tracing: light is only found by scattered rays happening to hit it, as in the book.
+ * Ignores the light list (analytic lights don't show up at all). Noisier with small lights, but simple, which
+ * makes it a good reference for checking the other integrators.
+ */
+pub struct SimplePathTracer;
+
+impl Integrator for SimplePathTracer {
+    fn li(&self, r: Ray, context: &RenderContext) -> Color {
+        trace_path(r, context, false)
+    }
+}
+
+/*
+ * Ambient occlusion: how open the surface seen by a camera ray is, ignoring materials and lights.
+ *
+ * Rays are sent from the first hit in cosine-weighted directions around the normal, and the result is the
+ * fraction that travel `max_distance` without hitting anything: white where the surface is fully open,
+ * darker in creases and contact points. Rays that miss the scene are white.
+ */
+pub struct AmbientOcclusion {
+    max_distance: f64, // How far away geometry still occludes
+    samples: u32, // Occlusion rays per camera ray
+}
+
+impl AmbientOcclusion {
+    pub fn new(max_distance: f64) -> Self {
+        Self { max_distance, samples: 1 }
+    }
+
+    // Sends several occlusion rays per camera ray (less noise for the same number of camera samples)
+    pub fn with_samples(mut self, samples: u32) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn li(&self, r: Ray, context: &RenderContext) -> Color {
+        let mut rec = HitRecord::default();
+        if !context.world.hit(&r, Interval::new(0.001, INFINITY), &mut rec) {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+
+        // The normal faces the camera ray, so the rays leave from the side that was seen
+        let frame = Onb::new(rec.normal);
+        let unoccluded = (0..self.samples)
+            .filter(|_| {
+                let occlusion_ray = Ray::new(rec.p, frame.transform(random_cosine_direction()));
+                let mut occluder_rec = HitRecord::default();
+                !context.world.hit(&occlusion_ray, Interval::new(0.001, self.max_distance), &mut occluder_rec)
+            })
+            .count();
+
+        let fraction = unoccluded as f64 / self.samples as f64;
+        Color::new(fraction, fraction, fraction)
+    }
+}
+
+/*
+ * Follows a path from the camera, adding up the light found along it.
+ *
+ * Color diffusion:
+ * If a ray bounces off a material and keeps 100% of its color, then it's white.
+ * If a ray bounces off a material and keeps 0% of its color, then it's black.
+ *
+ * The path is followed in a loop, carrying the throughput (product of the weights so far) instead of
+ * recursing, so deep paths through lots of glass can't overflow the stack. bsdf_pdf is the density the
+ * material picked the current ray with, or None if that direction wasn't also covered by light sampling
+ * (camera rays, specular bounces), so the emission it hits counts in full.
+ *
+ * Russian roulette: after `russian_roulette_depth` bounces, a path continues with a probability equal to its
+ * throughput (at most 0.95) and is divided by that probability if it does. Dim paths, which can't add much,
+ * mostly stop early, and the result stays unbiased because the survivors make up for them.
+ */
+fn trace_path(r: Ray, context: &RenderContext, light_sampling: bool) -> Color {
+    let world = context.world;
+    let lights = context.lights;
+
+    let mut radiance = Color::zero();
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    let mut r = r;
+    let mut bsdf_pdf: Option<f64> = None;
+
+    // No more light gathered if max ray bounce depth is reached
+    for bounce in 0..context.max_depth {
+        let mut rec = HitRecord::default();
+        // A ray attemps to accurately calculate the intersection point when intersecting with a hittable
+        // Someones this calculation is not accurate (floating point rounding error) so we add a small epsilon
+        // This fixes the "shadow acne" problem
+        let hit = world.hit(&r, Interval::new(0.001, INFINITY), &mut rec);
+
+        // Inside a medium the ray may scatter before reaching the next surface (random walk)
+        let mut scattered_in_medium = None;
+        if let Some(medium) = r.medium() {
+            match medium.sample(&r, if hit { rec.t } else { INFINITY }) {
+                MediumEvent::Scatter { p, weight } => {
+                    let mut scattered = Ray::new(p, medium.sample_phase(r.direction().unit_vector()));
+                    scattered.set_medium(Some(medium));
+                    scattered.set_wavelengths(r.wavelengths());
+                    throughput = throughput * weight;
+                    scattered_in_medium = Some(scattered);
+                }
+                MediumEvent::Pass { weight } => throughput = throughput * weight,
+            }
+        }
+
+        if let Some(scattered) = scattered_in_medium {
+            r = scattered;
+            bsdf_pdf = None;
+        } else {
+            if !hit {
+                // If no hit, keep the background (the sky isn't in the light list, so it always counts)
+                radiance += throughput * to_path_space(context.background(&r), &r);
+                break;
+            }
+
+            let mut emitted = to_path_space(rec.mat.emitted(&rec), &r);
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                if !emitted.near_zero() {
+                    emitted *= power_heuristic(bsdf_pdf, lights.shape_pdf(&r, &rec));
+                }
+            }
+            radiance += throughput * emitted;
+
+            // Shadow rays don't account for media, so bounces inside one only gather light by scattering.
+            // Lights are sampled whichever lobe the material picks (or if it absorbs the ray), since
+            // the shadow ray estimates the light reflected by all the non-specular lobes at once.
+            let sample_lights = light_sampling && !lights.is_empty() && r.medium().is_none();
+            if sample_lights {
+                radiance += throughput * sample_light(&r, &rec, world, lights);
+            }
+
+            let Some(sample) = rec.mat.sample(&r, &rec) else {
+                break;
+            };
+            let mut scattered = sample.scattered;
+            let mut weight = to_path_space(sample.weight, &r);
+
+            // The scattered ray stays on the same wavelengths, unless the material terminated the secondary ones
+            match (r.wavelengths(), scattered.wavelengths()) {
+                (Some(before), Some(after)) if !before.is_terminated() && after.is_terminated() => {
+                    weight = weight * SampledWavelengths::termination_weight();
+                }
+                (Some(before), None) => scattered.set_wavelengths(Some(before)),
+                _ => {}
+            }
+
+            // The pixel's footprint keeps spreading from its width at the hit (as if surfaces were flat)
+            scattered.set_cone(r.cone().map(|cone| RayCone { width: cone.width_at(rec.t * r.direction().length()), spread: cone.spread }));
+
+            throughput = throughput * weight;
+            bsdf_pdf = if sample_lights && !sample.is_specular { Some(sample.pdf) } else { None };
+            r = scattered;
+        }
+
+        if bounce + 1 >= context.russian_roulette_depth {
+            let survival = throughput.x().max(throughput.y()).max(throughput.z()).min(0.95);
+            if random_double() >= survival {
+                break;
+            }
+            throughput /= survival;
+        }
+    }
+
+    radiance
+}
+
+/*
+ * Direct light at a hit point from one shadow ray towards a random light: BSDF * cos * light / pdf,
+ * or nothing if something else is in the way.
+ * Light from shapes is weighted against the material sampling the same direction (see `trace_path`).
+ * Analytic lights can't be hit by scattered rays, so their samples count in full.
+ */
+fn sample_light(r: &Ray, rec: &HitRecord, world: &dyn Hittable, lights: &LightList) -> Color {
+    let Some(light) = lights.sample(rec.p) else {
+        return Color::zero();
+    };
+    let direction = match &light {
+        SampledLight::Shape { direction, .. } => direction.unit_vector(),
+        SampledLight::Analytic { sample, .. } => sample.direction,
+    };
+
+    let f = rec.mat.eval(r, rec, direction);
+    if f.near_zero() {
+        return Color::zero();
+    }
+
+    let mut shadow_ray = Ray::new(rec.p, direction);
+    shadow_ray.set_wavelengths(r.wavelengths());
+    let mut light_rec = HitRecord::default();
+
+    match light {
+        SampledLight::Shape { shape, pdf, .. } => {
+            // The sampled light only counts if nothing is in front of it
+            if !shape.hit(&shadow_ray, Interval::new(0.001, INFINITY), &mut light_rec) {
+                return Color::zero();
+            }
+            let mut occluder_rec = HitRecord::default();
+            if world.hit(&shadow_ray, Interval::new(0.001, light_rec.t - 0.001), &mut occluder_rec) {
+                return Color::zero();
+            }
+
+            let emitted = light_rec.mat.emitted(&light_rec);
+            let mis_weight = power_heuristic(pdf, rec.mat.pdf(r, rec, direction));
+            to_path_space(f, r) * to_path_space(emitted, r) * (mis_weight / pdf)
+        }
+        SampledLight::Analytic { sample, pdf } => {
+            if world.hit(&shadow_ray, Interval::new(0.001, sample.distance - 0.001), &mut light_rec) {
+                return Color::zero();
+            }
+            to_path_space(f, r) * to_path_space(sample.li, r) / pdf
+        }
+    }
+}
@@ -5,11 +5,13 @@ pub struct Interval {
     pub max: f64,
 }
 
-impl Interval {
+impl Default for Interval {
     fn default() -> Self {
         Self { min: INFINITY, max: -INFINITY }
     }
+}
 
+impl Interval {
     pub fn new(min: f64, max: f64) -> Self {
         Self { min, max }
     }
@@ -36,6 +38,3 @@ impl Interval {
         max: INFINITY,
     };
 }
-
-const empty: Interval = Interval::EMPTY;
-const universe: Interval = Interval::UNIVERSE;
\ No newline at end of file
@@ -0,0 +1,247 @@
+/*
+ * Layered and blended materials
+ *
+ * Both combine other materials stochastically: every scatter picks one of the parts to interact with,
+ * with a probability equal to its share of the reflected light. Because the pick probability is the
+ * weight itself, the picked material's attenuation can be used unchanged and the average over many
+ * samples converges to the blend.
+ */
+
+use std::sync::Arc;
+
+use super::{color::Color, hittable::HitRecord, material::{scatter_from_sample, BsdfSample, Material}, microfacet::{fresnel_dielectric, reflection_half_vector, TrowbridgeReitz}, onb::Onb, ray::Ray, texture::{IntoTexture, Texture}, utils::random_double, vec3::{reflect, Point3, Vec3}};
+
+/*
+ * Blend of two materials: weight 0 is all `a`, weight 1 is all `b`.
+ * A textured weight (red channel) lets materials be painted onto a surface, e.g. rust patches on metal.
+ */
+pub struct MixMaterial {
+    a: Arc<dyn Material>,
+    b: Arc<dyn Material>,
+    weight: Arc<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(a: Arc<dyn Material>, b: Arc<dyn Material>, weight: impl IntoTexture) -> Self {
+        Self { a, b, weight: weight.into_texture() }
+    }
+
+    fn weight(&self, rec: &HitRecord) -> f64 {
+        self.weight.value_at(rec).x().clamp(0.0, 1.0)
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        if random_double() < self.weight(rec) {
+            self.b.scatter(r_in, rec, attenuation, scattered)
+        } else {
+            self.a.scatter(r_in, rec, attenuation, scattered)
+        }
+    }
+
+    /*
+     * Samples one of the materials, then (for non-specular samples) re-weights the direction by the blended
+     * eval and pdf, so the sample agrees with `eval`/`pdf` of the mix as a whole.
+     */
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let picked = if random_double() < self.weight(rec) { &self.b } else { &self.a };
+        let mut sample = picked.sample(r_in, rec)?;
+        if sample.is_specular {
+            return Some(sample);
+        }
+
+        let direction = sample.scattered.direction();
+        sample.pdf = self.pdf(r_in, rec, direction);
+        if sample.pdf <= 0.0 {
+            return None;
+        }
+        sample.weight = self.eval(r_in, rec, direction) / sample.pdf;
+        Some(sample)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        let weight = self.weight(rec);
+        (1.0 - weight) * self.a.eval(r_in, rec, direction) + weight * self.b.eval(r_in, rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let weight = self.weight(rec);
+        (1.0 - weight) * self.a.pdf(r_in, rec, direction) + weight * self.b.pdf(r_in, rec, direction)
+    }
+
+    // Opaque where the picked material is, so a cut out part of either one lets through its share of the rays
+    fn is_opaque_at(&self, rec: &HitRecord) -> bool {
+        if random_double() < self.weight(rec) {
+            self.b.is_opaque_at(rec)
+        } else {
+            self.a.is_opaque_at(rec)
+        }
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        let weight = self.weight(rec);
+        (1.0 - weight) * self.a.emitted(rec) + weight * self.b.emitted(rec)
+    }
+
+    // Blended with the weight at the middle of its texture
+    fn average_emitted(&self) -> Color {
+        let weight = self.weight.value(0.5, 0.5, &Point3::zero()).x().clamp(0.0, 1.0);
+        (1.0 - weight) * self.a.average_emitted() + weight * self.b.average_emitted()
+    }
+
+    fn albedo(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        let weight = self.weight(rec);
+        (1.0 - weight) * self.a.albedo(r_in, rec) + weight * self.b.albedo(r_in, rec)
+    }
+}
+
+/*
+ * Clear dielectric coat over any base material (car paint, varnished wood, lacquered plastic).
+ *
+ * The coat is a GGX microfacet reflection lobe with the exact dielectric Fresnel term. The light it doesn't
+ * reflect (1 - F at the surface normal) passes through to the base material. The coat can be tinted, which
+ * filters the light that went through it.
+ *
+ * Samples pick the coat with that Fresnel reflectance, otherwise the base. Non-specular samples are then
+ * weighted by the BSDF of the whole stack, like MixMaterial, so the coat and base agree with `eval`/`pdf`.
+ * A perfectly smooth coat is a mirror, and is sampled as a specular reflection.
+ */
+pub struct Coated {
+    base: Arc<dyn Material>,
+    refraction_index: f64,
+    roughness: Arc<dyn Texture>,
+    tint: Arc<dyn Texture>, // Color of the coat, applied to light reaching the base and coming back out
+}
+
+impl Coated {
+    pub fn new(base: Arc<dyn Material>, refraction_index: f64, roughness: impl IntoTexture) -> Self {
+        Self {
+            base,
+            refraction_index,
+            roughness: roughness.into_texture(),
+            tint: Color::new(1.0, 1.0, 1.0).into_texture(),
+        }
+    }
+
+    pub fn with_tint(mut self, tint: impl IntoTexture) -> Self {
+        self.tint = tint.into_texture();
+        self
+    }
+}
+
+// The coat at a front face hit, in the local shading frame
+struct Coat {
+    frame: Onb,
+    wo: Vec3,
+    distribution: TrowbridgeReitz,
+    reflectance: f64, // Fresnel reflectance at the surface normal, the probability of sampling the coat
+}
+
+impl Coated {
+    // None on back faces (the coat is only on the outside) and for rays arriving from below the surface
+    fn coat(&self, r_in: &Ray, rec: &HitRecord) -> Option<Coat> {
+        if !rec.front_face {
+            return None;
+        }
+
+        let frame = Onb::from_normal_tangent(rec.normal, rec.tangent);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let roughness = self.roughness.value_at(rec).x().clamp(0.0, 1.0);
+        let alpha = f64::max(TrowbridgeReitz::roughness_to_alpha(roughness), 1e-4);
+        let reflectance = fresnel_dielectric(wo.z(), self.refraction_index);
+        Some(Coat { frame, wo, distribution: TrowbridgeReitz::new(alpha, alpha), reflectance })
+    }
+
+    // Coat reflection lobe: BSDF times cos theta_i and sampling density, zero for a smooth (mirror) coat
+    fn coat_lobe(&self, coat: &Coat, direction: Vec3) -> (f64, f64) {
+        if coat.distribution.effectively_smooth() {
+            return (0.0, 0.0);
+        }
+        let wi = coat.frame.to_local(direction.unit_vector());
+        let Some(wm) = reflection_half_vector(coat.wo, wi) else {
+            return (0.0, 0.0);
+        };
+
+        let f = fresnel_dielectric(coat.wo.dot(&wm), self.refraction_index);
+        (f * coat.distribution.reflection_eval(coat.wo, wi, wm), coat.distribution.reflection_pdf(coat.wo, wm))
+    }
+}
+
+impl Material for Coated {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        scatter_from_sample(self.sample(r_in, rec), attenuation, scattered)
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let Some(coat) = self.coat(r_in, rec) else {
+            return self.base.sample(r_in, rec);
+        };
+
+        let mut sample = if random_double() < coat.reflectance {
+            let wm = coat.distribution.sample_wm(coat.wo, random_double(), random_double());
+            let wi = reflect(-coat.wo, wm);
+            if wi.z() <= 0.0 {
+                return None;
+            }
+
+            let scattered = Ray::new(rec.p, coat.frame.transform(wi));
+            if coat.distribution.effectively_smooth() {
+                return Some(BsdfSample { scattered, weight: Color::new(1.0, 1.0, 1.0), pdf: 0.0, is_specular: true });
+            }
+            BsdfSample { scattered, weight: Color::zero(), pdf: 0.0, is_specular: false }
+        } else {
+            // Through the coat to the base, which was picked with the probability of its weight
+            let mut sample = self.base.sample(r_in, rec)?;
+            if sample.is_specular {
+                sample.weight = sample.weight * self.tint.value_at(rec);
+                return Some(sample);
+            }
+            sample
+        };
+
+        let direction = sample.scattered.direction();
+        sample.pdf = self.pdf(r_in, rec, direction);
+        if sample.pdf <= 0.0 {
+            return None;
+        }
+        sample.weight = self.eval(r_in, rec, direction) / sample.pdf;
+        Some(sample)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        let Some(coat) = self.coat(r_in, rec) else {
+            return self.base.eval(r_in, rec, direction);
+        };
+
+        let tint = self.tint.value_at(rec);
+        let (coat_f, _) = self.coat_lobe(&coat, direction);
+        coat_f * Color::new(1.0, 1.0, 1.0) + (1.0 - coat.reflectance) * tint * self.base.eval(r_in, rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let Some(coat) = self.coat(r_in, rec) else {
+            return self.base.pdf(r_in, rec, direction);
+        };
+
+        let (_, coat_pdf) = self.coat_lobe(&coat, direction);
+        coat.reflectance * coat_pdf + (1.0 - coat.reflectance) * self.base.pdf(r_in, rec, direction)
+    }
+
+    fn is_opaque_at(&self, rec: &HitRecord) -> bool {
+        self.base.is_opaque_at(rec)
+    }
+
+    // The coat is clear, so an emissive base shines through it unchanged
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.base.emitted(rec)
+    }
+
+    fn average_emitted(&self) -> Color {
+        self.base.average_emitted()
+    }
+}
@@ -0,0 +1,618 @@
+/*
+ * Lights
+ *
+ * Analytic lights are described by a position or direction instead of geometry, the way lighting designers
+ * set up a scene: point lights (bulbs), spot lights (stage lights, flashlights) and directional lights (sun).
+ * They aren't in the world, so camera and scattered rays never hit them: they only light the scene through
+ * next-event estimation (shadow rays sent by the camera integrator).
+ *
+ * The light list holds them together with the emissive shapes that light sampling should aim for.
+ */
+
+use std::sync::{Arc, OnceLock};
+
+use super::{color::{luminance, Color}, hittable::{HitRecord, Hittable}, ies::IesProfile, interval::Interval, onb::Onb, ray::Ray, utils::{random_double, INFINITY, PI}, vec3::{Point3, Vec3}};
+
+/*
+ * Light arriving at a point from a sampled direction.
+ *
+ * li is the incident light divided by the density of picking that direction, so the direct light is just
+ * BSDF eval * li if nothing blocks the shadow ray before `distance`.
+ */
+pub struct LightSample {
+    pub direction: Vec3, // Unit direction from the shaded point towards the light
+    pub distance: f64, // Distance to the light along direction, INFINITY for directional lights
+    pub li: Color,
+}
+
+pub trait Light: Send + Sync {
+    // Samples light arriving at p, or None if no light reaches it (e.g. outside a spot light's cone)
+    fn sample(&self, p: Point3) -> Option<LightSample>;
+
+    // Position and power, None for lights infinitely far away
+    fn bounds(&self) -> Option<LightBounds>;
+
+    // Rough power a light without bounds delivers to a scene of the given radius, None if it can't tell
+    fn unbounded_power(&self, _scene_radius: f64) -> Option<f64> {
+        None
+    }
+}
+
+/*
+ * Point light: shines equally in all directions from a single point.
+ *
+ * intensity is the light per unit solid angle, falling off with the inverse square of the distance.
+ * An optional range fades it smoothly to zero, which real bulbs don't do but keeps many local lights cheap
+ * to art direct.
+ *
+ * With an IES profile the intensity is its brightest direction, and the profile shapes the rest.
+ */
+pub struct PointLight {
+    position: Point3,
+    intensity: Color,
+    range: Option<f64>, // Distance at which the light has faded out completely, None for physical falloff
+    profile: Option<Profile>,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> Self {
+        Self { position, intensity, range: None, profile: None }
+    }
+
+    pub fn with_range(mut self, range: f64) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /*
+     * aim: where the profile's nadir (vertical angle 0) points, usually straight down for ceiling fixtures.
+     * reference: where horizontal angle 0 points (projected perpendicular to aim), e.g. along a linear fixture.
+     */
+    pub fn with_ies(mut self, profile: Arc<IesProfile>, aim: Vec3, reference: Vec3) -> Self {
+        self.profile = Some(Profile::new(profile, aim, reference));
+        self
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: Point3) -> Option<LightSample> {
+        let (direction, distance) = towards(self.position, p)?;
+        let shape = self.profile.as_ref().map_or(1.0, |profile| profile.evaluate(-direction));
+        if shape <= 0.0 {
+            return None;
+        }
+
+        let li = self.intensity * (shape * distance_falloff(distance, self.range));
+        Some(LightSample { direction, distance, li })
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let solid_angle = self.profile.as_ref().map_or(4.0 * PI, |profile| profile.ies.integral());
+        Some(LightBounds { center: self.position, radius: 0.0, power: solid_angle * luminance(self.intensity) })
+    }
+}
+
+/*
+ * Spot light: a point light restricted to a cone.
+ *
+ * cone_angle is the angle in degrees from the axis to the edge of the cone. The light fades out over the
+ * last `penumbra` degrees inside the cone (0 gives a hard edge).
+ *
+ * An IES profile is aimed along the spot's axis and applies on top of the cone.
+ */
+pub struct SpotLight {
+    position: Point3,
+    axis: Vec3, // Unit direction the spot points to
+    intensity: Color,
+    cos_outer: f64, // Cosine of the cone angle, no light outside
+    cos_inner: f64, // Cosine of the angle where the penumbra starts, full intensity inside
+    range: Option<f64>,
+    profile: Option<Profile>,
+}
+
+impl SpotLight {
+    pub fn new(position: Point3, target: Point3, intensity: Color, cone_angle: f64, penumbra: f64) -> Self {
+        let outer = cone_angle.clamp(0.0, 180.0);
+        let inner = (outer - penumbra.max(0.0)).max(0.0);
+        Self {
+            position,
+            axis: (target - position).unit_vector(),
+            intensity,
+            cos_outer: f64::cos(f64::to_radians(outer)),
+            cos_inner: f64::cos(f64::to_radians(inner)),
+            range: None,
+            profile: None,
+        }
+    }
+
+    pub fn with_range(mut self, range: f64) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    // reference: where horizontal angle 0 points (projected perpendicular to the spot's axis)
+    pub fn with_ies(mut self, profile: Arc<IesProfile>, reference: Vec3) -> Self {
+        self.profile = Some(Profile::new(profile, self.axis, reference));
+        self
+    }
+
+    // Smooth fade from 1 inside the inner cone to 0 at the cone's edge
+    fn cone_falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_inner {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0;
+        }
+        let x = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        x * x * (3.0 - 2.0 * x)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, p: Point3) -> Option<LightSample> {
+        let (direction, distance) = towards(self.position, p)?;
+        let shape = self.profile.as_ref().map_or(1.0, |profile| profile.evaluate(-direction));
+        let falloff = shape * self.cone_falloff(-direction.dot(&self.axis));
+        if falloff <= 0.0 {
+            return None;
+        }
+
+        let li = self.intensity * (falloff * distance_falloff(distance, self.range));
+        Some(LightSample { direction, distance, li })
+    }
+
+    // Solid angle of the cone, counting the penumbra as half lit (or the profile's, if that's smaller)
+    fn bounds(&self) -> Option<LightBounds> {
+        let mut solid_angle = 2.0 * PI * (1.0 - 0.5 * (self.cos_outer + self.cos_inner));
+        if let Some(profile) = &self.profile {
+            solid_angle = solid_angle.min(profile.ies.integral());
+        }
+        Some(LightBounds { center: self.position, radius: 0.0, power: solid_angle * luminance(self.intensity) })
+    }
+}
+
+/*
+ * Directional light: parallel light from infinitely far away, like the sun.
+ *
+ * irradiance is the light arriving on a surface facing it. With an angular diameter (the sun is about
+ * 0.53 degrees) the light comes from a small disk in the sky instead of a single direction, which gives
+ * shadows soft edges that widen with the distance to the occluder.
+ */
+pub struct DirectionalLight {
+    direction: Vec3, // Unit direction the light travels in
+    irradiance: Color,
+    cos_max: f64, // Cosine of the disk's angular radius, 1 for a single direction
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Color) -> Self {
+        Self { direction: direction.unit_vector(), irradiance, cos_max: 1.0 }
+    }
+
+    pub fn with_angular_diameter(mut self, degrees: f64) -> Self {
+        self.cos_max = f64::cos(f64::to_radians(degrees.clamp(0.0, 180.0) / 2.0));
+        self
+    }
+}
+
+impl Light for DirectionalLight {
+    /*
+     * Directions are picked uniformly over the disk's cone, whose radiance is irradiance / solid angle,
+     * so radiance / pdf is the irradiance again.
+     */
+    fn sample(&self, _p: Point3) -> Option<LightSample> {
+        let to_light = -self.direction;
+        let direction = if self.cos_max >= 1.0 {
+            to_light
+        } else {
+            let z = 1.0 + random_double() * (self.cos_max - 1.0);
+            let phi = 2.0 * PI * random_double();
+            let r = f64::sqrt(1.0 - z * z);
+            Onb::new(to_light).transform(Vec3::new(r * f64::cos(phi), r * f64::sin(phi), z))
+        };
+
+        Some(LightSample { direction, distance: INFINITY, li: self.irradiance })
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+
+    // The irradiance falling on the scene's cross section
+    fn unbounded_power(&self, scene_radius: f64) -> Option<f64> {
+        Some(luminance(self.irradiance) * PI * scene_radius * scene_radius)
+    }
+}
+
+/*
+ * Where a light is and how much light it gives off, for picking lights worth sampling.
+ * Only needs to be roughly right: it changes noise, not the converged image.
+ */
+#[derive(Clone, Copy)]
+pub struct LightBounds {
+    pub center: Point3,
+    pub radius: f64, // Radius of a sphere around center containing the whole light
+    pub power: f64, // Total emitted power (luminance)
+}
+
+/*
+ * Lights the camera integrator samples directly.
+ *
+ * shapes: emissive objects of the world (area lights). They can also be hit by scattered rays,
+ *         so they're weighted against BSDF sampling.
+ * lights: analytic lights, only reachable through light sampling.
+ *
+ * Each shadow ray goes to one light. Lights with bounds are organized in a bounding volume hierarchy (the
+ * light tree), which is walked from the root towards lights that are bright and close to the shaded point:
+ * each step picks a child with probability proportional to power / distance^2. This keeps noise low with
+ * hundreds of lights, where most of them barely light any given point. Unbounded lights (directional lights,
+ * shapes that can't report bounds) compete with the tree as a whole, in proportion to their power: the light
+ * they deliver to the tree's bounds (which stand in for the scene) against the tree's total power. Lights that
+ * can't estimate that get the same share as the tree.
+ *
+ * Note: The importance ignores which way lights face, so e.g. spot lights pointing away still get picked.
+ */
+pub struct LightList {
+    shapes: Vec<Arc<dyn Hittable>>,
+    lights: Vec<Arc<dyn Light>>,
+    tree: OnceLock<LightTree>, // Built on first use, so adding lights stays cheap
+}
+
+/*
+ * The light picked for a shadow ray.
+ *
+ * Shape: the direction points at an emissive shape, pdf is its solid angle density (including the chance
+ *        of picking that shape), and the light is the shape's emission if the shadow ray reaches it.
+ * Analytic: an analytic light's sample, with the probability of having picked that light.
+ */
+pub enum SampledLight<'a> {
+    Shape { shape: &'a dyn Hittable, direction: Vec3, pdf: f64 },
+    Analytic { sample: LightSample, pdf: f64 },
+}
+
+#[derive(Clone, Copy)]
+enum LightRef {
+    Shape(usize),
+    Analytic(usize),
+}
+
+impl Default for LightList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LightList {
+    pub fn new() -> Self {
+        Self { shapes: Vec::new(), lights: Vec::new(), tree: OnceLock::new() }
+    }
+
+    // Emissive shape to sample, which must also be in the world
+    pub fn add_shape(&mut self, shape: Arc<dyn Hittable>) {
+        self.shapes.push(shape);
+        self.tree = OnceLock::new();
+    }
+
+    pub fn add(&mut self, light: Arc<dyn Light>) {
+        self.lights.push(light);
+        self.tree = OnceLock::new();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty() && self.lights.is_empty()
+    }
+
+    fn tree(&self) -> &LightTree {
+        self.tree.get_or_init(|| {
+            let shapes = self.shapes.iter().enumerate().map(|(i, shape)| (LightRef::Shape(i), shape.light_bounds()));
+            let lights = self.lights.iter().enumerate().map(|(i, light)| (LightRef::Analytic(i), light.bounds()));
+            LightTree::build(shapes.chain(lights).collect(), |light, scene_radius| match light {
+                LightRef::Analytic(i) => self.lights[i].unbounded_power(scene_radius),
+                LightRef::Shape(_) => None,
+            })
+        })
+    }
+
+    pub fn sample(&self, p: Point3) -> Option<SampledLight<'_>> {
+        let tree = self.tree();
+        let (light, pmf) = tree.pick(p)?;
+
+        match light {
+            LightRef::Shape(i) => {
+                let shape = self.shapes[i].as_ref();
+                let direction = shape.random(p);
+                let pdf = pmf * shape.pdf_value(p, direction);
+                if pdf <= 0.0 {
+                    return None;
+                }
+                Some(SampledLight::Shape { shape, direction, pdf })
+            }
+            LightRef::Analytic(i) => {
+                let sample = self.lights[i].sample(p)?;
+                Some(SampledLight::Analytic { sample, pdf: pmf })
+            }
+        }
+    }
+
+    /*
+     * Solid angle density with which `sample` would have picked the direction of `r` towards the emissive shape
+     * it hit (rec), as seen from the ray's origin.
+     *
+     * The shape is found by looking for lights whose bounds contain the hit point and that the ray hits at the same t.
+     */
+    pub fn shape_pdf(&self, r: &Ray, rec: &HitRecord) -> f64 {
+        if self.shapes.is_empty() {
+            return 0.0;
+        }
+
+        let tree = self.tree();
+        let origin = r.origin();
+        let mut pdf = 0.0;
+        for (light, pmf) in tree.candidates(origin, rec.p) {
+            let LightRef::Shape(i) = light else {
+                continue;
+            };
+            let shape = &self.shapes[i];
+
+            let mut shape_rec = HitRecord::default();
+            let same_hit = shape.hit(r, Interval::new(0.001, INFINITY), &mut shape_rec) && (shape_rec.t - rec.t).abs() <= 1e-6 * rec.t.max(1.0);
+            if same_hit {
+                pdf += pmf * shape.pdf_value(origin, r.direction());
+            }
+        }
+        pdf
+    }
+}
+
+/*
+ * Light tree: a binary bounding volume hierarchy over the bounded lights, plus the list of unbounded ones.
+ * Every node keeps a bounding sphere and the total power of the lights below it.
+ */
+struct LightTree {
+    nodes: Vec<LightNode>, // Root first
+    leaf_of: Vec<(LightRef, usize)>, // Leaf node of each bounded light
+    unbounded: Vec<(LightRef, f64)>, // With their weight for the first pick
+    total_weight: f64, // Of the unbounded lights and the tree (its root power)
+}
+
+struct LightNode {
+    bounds: LightBounds,
+    parent: Option<usize>,
+    kind: LightNodeKind,
+}
+
+enum LightNodeKind {
+    Leaf(LightRef),
+    Interior(usize, usize),
+}
+
+impl LightTree {
+    // `unbounded_power` estimates the power of an unbounded light for a scene radius
+    fn build(lights: Vec<(LightRef, Option<LightBounds>)>, unbounded_power: impl Fn(LightRef, f64) -> Option<f64>) -> Self {
+        let mut tree = Self { nodes: Vec::new(), leaf_of: Vec::new(), unbounded: Vec::new(), total_weight: 0.0 };
+
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        for (light, bounds) in lights {
+            match bounds {
+                Some(bounds) if bounds.power > 0.0 => bounded.push((light, bounds)),
+                Some(_) => {} // Doesn't emit anything, never worth a shadow ray
+                None => unbounded.push(light),
+            }
+        }
+
+        if !bounded.is_empty() {
+            tree.build_node(&mut bounded, None);
+        }
+
+        let tree_power = tree.tree_weight();
+        let scene_radius = tree.nodes.first().map_or(0.0, |root| root.bounds.radius);
+        let fallback = if tree_power > 0.0 { tree_power } else { 1.0 };
+        tree.unbounded = unbounded
+            .into_iter()
+            .map(|light| (light, unbounded_power(light, scene_radius).filter(|&power| power > 0.0).unwrap_or(fallback)))
+            .collect();
+        tree.total_weight = tree_power + tree.unbounded.iter().map(|&(_, weight)| weight).sum::<f64>();
+        tree
+    }
+
+    // Splits the lights at the median of their centers along the widest axis, returns the new node's index
+    fn build_node(&mut self, lights: &mut [(LightRef, LightBounds)], parent: Option<usize>) -> usize {
+        let index = self.nodes.len();
+
+        if lights.len() == 1 {
+            let (light, bounds) = lights[0];
+            self.nodes.push(LightNode { bounds, parent, kind: LightNodeKind::Leaf(light) });
+            self.leaf_of.push((light, index));
+            return index;
+        }
+
+        let bounds = enclosing_bounds(lights);
+        self.nodes.push(LightNode { bounds, parent, kind: LightNodeKind::Leaf(lights[0].0) });
+
+        let axis = widest_axis(lights);
+        lights.sort_by(|a, b| axis_value(a.1.center, axis).total_cmp(&axis_value(b.1.center, axis)));
+        let (left, right) = lights.split_at_mut(lights.len() / 2);
+        let left = self.build_node(left, Some(index));
+        let right = self.build_node(right, Some(index));
+        self.nodes[index].kind = LightNodeKind::Interior(left, right);
+
+        index
+    }
+
+    // Weight of the tree as a whole in the first pick: the power of its root
+    fn tree_weight(&self) -> f64 {
+        self.nodes.first().map_or(0.0, |root| root.bounds.power)
+    }
+
+    fn pick(&self, p: Point3) -> Option<(LightRef, f64)> {
+        if self.total_weight <= 0.0 {
+            return None;
+        }
+
+        // The first pick is between the unbounded lights and the tree as a whole, by weight
+        let mut u = random_double() * self.total_weight;
+        for &(light, weight) in self.unbounded.iter() {
+            if u < weight {
+                return Some((light, weight / self.total_weight));
+            }
+            u -= weight;
+        }
+        if self.nodes.is_empty() {
+            // Rounding left u past the last unbounded light
+            let &(light, weight) = self.unbounded.last()?;
+            return Some((light, weight / self.total_weight));
+        }
+
+        // Walk down the tree towards the more important child
+        let mut pmf = self.tree_weight() / self.total_weight;
+        let mut node = 0;
+        loop {
+            match self.nodes[node].kind {
+                LightNodeKind::Leaf(light) => return Some((light, pmf)),
+                LightNodeKind::Interior(left, right) => {
+                    let left_importance = self.importance(left, p);
+                    let right_importance = self.importance(right, p);
+                    let total = left_importance + right_importance;
+                    if total <= 0.0 {
+                        return None;
+                    }
+
+                    let p_left = left_importance / total;
+                    if random_double() < p_left {
+                        node = left;
+                        pmf *= p_left;
+                    } else {
+                        node = right;
+                        pmf *= 1.0 - p_left;
+                    }
+                }
+            }
+        }
+    }
+
+    // Probability that `pick` from p ends at the given leaf: the product of the choices on the way up to the root
+    fn leaf_pmf(&self, leaf: usize, p: Point3) -> f64 {
+        let mut pmf = self.tree_weight() / self.total_weight;
+        let mut node = leaf;
+        while let Some(parent) = self.nodes[node].parent {
+            if let LightNodeKind::Interior(left, right) = self.nodes[parent].kind {
+                let sibling = if left == node { right } else { left };
+                let importance = self.importance(node, p);
+                let total = importance + self.importance(sibling, p);
+                if total <= 0.0 {
+                    return 0.0;
+                }
+                pmf *= importance / total;
+            }
+            node = parent;
+        }
+        pmf
+    }
+
+    // Lights that could have emitted from `hit` (unbounded ones, and bounded ones containing it), with the pmf of picking each from p
+    fn candidates(&self, p: Point3, hit: Point3) -> Vec<(LightRef, f64)> {
+        let mut candidates: Vec<(LightRef, f64)> = self.unbounded.iter().map(|&(light, weight)| (light, weight / self.total_weight)).collect();
+
+        if self.nodes.is_empty() {
+            return candidates;
+        }
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let bounds = self.nodes[node].bounds;
+            if (hit - bounds.center).length() > bounds.radius * (1.0 + 1e-6) + 1e-6 {
+                continue;
+            }
+            match self.nodes[node].kind {
+                LightNodeKind::Leaf(light) => candidates.push((light, self.leaf_pmf(node, p))),
+                LightNodeKind::Interior(left, right) => stack.extend([left, right]),
+            }
+        }
+        candidates
+    }
+
+    // How much light a node's lights could bring to p: power / distance^2, using the bounding radius up close
+    fn importance(&self, node: usize, p: Point3) -> f64 {
+        let bounds = self.nodes[node].bounds;
+        let distance_squared = (bounds.center - p).length_squared();
+        bounds.power / distance_squared.max(bounds.radius * bounds.radius).max(1e-8)
+    }
+}
+
+// Bounding sphere around a set of bounding spheres (around their box), with their total power
+fn enclosing_bounds(lights: &[(LightRef, LightBounds)]) -> LightBounds {
+    let mut min = Vec3::new(INFINITY, INFINITY, INFINITY);
+    let mut max = Vec3::new(-INFINITY, -INFINITY, -INFINITY);
+    for (_, bounds) in lights {
+        let r = Vec3::new(bounds.radius, bounds.radius, bounds.radius);
+        let (lo, hi) = (bounds.center - r, bounds.center + r);
+        min = Vec3::new(min.x().min(lo.x()), min.y().min(lo.y()), min.z().min(lo.z()));
+        max = Vec3::new(max.x().max(hi.x()), max.y().max(hi.y()), max.z().max(hi.z()));
+    }
+
+    let center = 0.5 * (min + max);
+    let radius = lights.iter().map(|(_, bounds)| (bounds.center - center).length() + bounds.radius).fold(0.0, f64::max);
+    let power = lights.iter().map(|(_, bounds)| bounds.power).sum();
+    LightBounds { center, radius, power }
+}
+
+fn widest_axis(lights: &[(LightRef, LightBounds)]) -> usize {
+    let extent = |axis: usize| {
+        let values = lights.iter().map(|(_, bounds)| axis_value(bounds.center, axis));
+        let (min, max) = values.fold((INFINITY, -INFINITY), |(min, max), v| (min.min(v), max.max(v)));
+        max - min
+    };
+    (0..3).max_by(|&a, &b| extent(a).total_cmp(&extent(b))).unwrap_or(0)
+}
+
+fn axis_value(v: Vec3, axis: usize) -> f64 {
+    match axis {
+        0 => v.x(),
+        1 => v.y(),
+        _ => v.z(),
+    }
+}
+
+// An IES profile placed in the world, with its nadir along the frame's w axis and horizontal angle 0 along u
+struct Profile {
+    ies: Arc<IesProfile>,
+    frame: Onb,
+}
+
+impl Profile {
+    fn new(ies: Arc<IesProfile>, aim: Vec3, reference: Vec3) -> Self {
+        Self { ies, frame: Onb::from_normal_tangent(aim, reference) }
+    }
+
+    // Relative intensity towards a world space direction leaving the light
+    fn evaluate(&self, direction: Vec3) -> f64 {
+        self.ies.evaluate(self.frame.to_local(direction))
+    }
+}
+
+// Unit direction and distance from p to a light at position, None if p is at the light
+fn towards(position: Point3, p: Point3) -> Option<(Vec3, f64)> {
+    let to_light = position - p;
+    let distance = to_light.length();
+    if distance <= 0.0 {
+        return None;
+    }
+    Some((to_light / distance, distance))
+}
+
+/*
+ * Inverse square falloff. With a range it's windowed so it smoothly reaches 0 at the range:
+ * (1 - (d / range)^4)^2 / d^2 (the usual game engine window, close to physical near the light).
+ */
+fn distance_falloff(distance: f64, range: Option<f64>) -> f64 {
+    let inverse_square = 1.0 / (distance * distance);
+    match range {
+        Some(range) => {
+            let ratio = distance / range;
+            let window = (1.0 - ratio * ratio * ratio * ratio).clamp(0.0, 1.0);
+            window * window * inverse_square
+        }
+        None => inverse_square,
+    }
+}
@@ -4,40 +4,205 @@
  * Needs to be able to:
  * 1. Produce a scattered ray (or say it absorbed the incident ray).
  * 2. If scattered, say how much the ray should be attenuated.
+ *
+ * Materials can also expose their BSDF for importance sampling (`sample`, `eval` and `pdf`), which lets the
+ * integrator weigh directions it picked itself (e.g. towards a light) against the material's own choice.
  */
 
-use super::{color::Color, hittable::HitRecord, ray::Ray, utils::random_double, vec3::{random_unit_vector, reflect, refract, Vec3}};
+use std::sync::Arc;
 
-pub trait Material {
+use super::{color::Color, hittable::HitRecord, onb::Onb, ray::Ray, spectral::Dispersion, texture::{IntoTexture, Texture}, utils::{random_double, PI}, vec3::{random_cosine_direction, random_unit_vector, reflect, refract, Point3, Vec3}};
+
+pub trait Material: Send + Sync {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool;
+
+    /*
+     * Whether the surface is actually there at this hit (alpha / opacity cutouts).
+     * Hittables call this with a filled in hit record before accepting a hit, and ignore the hit if it's false,
+     * so rays pass straight through cut out parts (leaves, fences, decals).
+     */
+    fn is_opaque_at(&self, _rec: &HitRecord) -> bool {
+        true
+    }
+
+    /*
+     * Samples a scattered direction from the BSDF, or None if the ray was absorbed.
+     *
+     * The default wraps `scatter` and marks the sample as specular: its direction can't be evaluated with
+     * `eval`/`pdf`, so it's used as-is and lights are never sampled for it. That's only right for delta
+     * distributions (smooth mirrors and glass). Materials with rough or diffuse lobes must override `sample`,
+     * `eval` and `pdf` together, or they won't be lit by light sampling (and analytic lights won't light them at all).
+     */
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let mut scattered = Ray::default();
+        let mut attenuation = Color::zero();
+        if !self.scatter(r_in, rec, &mut attenuation, &mut scattered) {
+            return None;
+        }
+        Some(BsdfSample { scattered, weight: attenuation, pdf: 0.0, is_specular: true })
+    }
+
+    // BSDF times |cos theta| for light arriving from `direction` (world space, pointing away from the surface)
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> Color {
+        Color::zero()
+    }
+
+    // Solid angle density with which `sample` picks `direction`
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    // Light emitted from the hit point back along the incoming ray
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        Color::zero()
+    }
+
+    // Rough average of `emitted` over the surface, used to estimate how bright a light is
+    fn average_emitted(&self) -> Color {
+        Color::zero()
+    }
+
+    /*
+     * Fraction of the light arriving along r_in that the surface keeps, i.e. its color without the lighting
+     * (the albedo AOV, which denoisers use to tell texture detail from noise).
+     *
+     * The default averages the weights of a few BSDF samples, leaving out absorbed ones (e.g. rough reflections
+     * going below the surface), so it's noisy for rough materials. Materials with a known color override it.
+     */
+    fn albedo(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        let mut sum = Color::zero();
+        let mut count = 0;
+        for _ in 0..ALBEDO_SAMPLES {
+            if let Some(sample) = self.sample(r_in, rec) {
+                sum += sample.weight;
+                count += 1;
+            }
+        }
+        if count == 0 { Color::zero() } else { sum / count as f64 }
+    }
+}
+
+// BSDF samples averaged by the default `Material::albedo`
+const ALBEDO_SAMPLES: u32 = 16;
+
+/*
+ * A direction sampled from a BSDF.
+ *
+ * weight is eval / pdf, i.e. what the path throughput is multiplied by (the attenuation of `scatter`).
+ * Specular samples come from a delta distribution (mirrors, glass, and materials only implementing `scatter`),
+ * so their pdf is meaningless and they can't be combined with directions sampled some other way.
+ */
+pub struct BsdfSample {
+    pub scattered: Ray,
+    pub weight: Color,
+    pub pdf: f64,
+    pub is_specular: bool,
+}
+
+// Fills in the outputs of `Material::scatter` from a BSDF sample, for materials implementing `sample`
+pub fn scatter_from_sample(sample: Option<BsdfSample>, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+    match sample {
+        Some(sample) => {
+            *attenuation = sample.weight;
+            *scattered = sample.scattered;
+            true
+        }
+        None => false,
+    }
 }
 
 /*
  * Lambertian (diffuse) reflection for modeling light attenuation.
  * Can either always scatter, sometimes scatter, or scatter with some probability.
+ *
+ * The albedo is a texture looked up at the hit's (u, v) and p, so a constant color is just a SolidColor (`new`).
+ *
+ * Directions are sampled proportional to cos theta around the normal, which cancels the cosine term:
+ * f * cos / pdf = (albedo / pi) * cos / (cos / pi) = albedo.
  */
 pub struct Lambertian {
-    albedo: Color,
+    tex: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
-        Self { albedo }
+        Self::from_texture(albedo)
+    }
+
+    pub fn from_texture(tex: impl IntoTexture) -> Self {
+        Self { tex: tex.into_texture() }
     }
 }
 
 impl Material for Lambertian {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
-        let mut scatter_direction = rec.normal + random_unit_vector();
-        
-        // Catch degenerate scatter direction
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
+        scatter_from_sample(self.sample(r_in, rec), attenuation, scattered)
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let frame = Onb::new(rec.normal);
+        let direction = frame.transform(random_cosine_direction());
+
+        Some(BsdfSample {
+            scattered: Ray::new(rec.p, direction),
+            weight: self.tex.value_at(rec),
+            pdf: self.pdf(r_in, rec, direction),
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.tex.value_at(rec) * self.pdf(r_in, rec, direction)
+    }
+
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let cos_theta = rec.normal.dot(&direction.unit_vector());
+        f64::max(cos_theta, 0.0) / PI
+    }
+
+    fn albedo(&self, _r_in: &Ray, rec: &HitRecord) -> Color {
+        self.tex.value_at(rec)
+    }
+}
+
+/*
+ * Diffuse area light
+ *
+ * Emits the same radiance in every direction from its front face and doesn't scatter.
+ * Colors above 1 are fine (and usually needed): they're a brightness, not a reflectance.
+ *
+ * Note: Add the shape to the camera's lights too, otherwise light sampling never finds it
+ *       and it only lights the scene through rays that happen to bounce into it.
+ */
+pub struct DiffuseLight {
+    tex: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self::from_texture(emit)
+    }
+
+    pub fn from_texture(tex: impl IntoTexture) -> Self {
+        Self { tex: tex.into_texture() }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _attenuation: &mut Color, _scattered: &mut Ray) -> bool {
+        false
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        if !rec.front_face {
+            return Color::zero();
         }
+        self.tex.value_at(rec)
+    }
 
-        *scattered = Ray::new(rec.p, scatter_direction);
-        *attenuation = self.albedo;
-        true
+    // Sampled at the middle of the texture
+    fn average_emitted(&self) -> Color {
+        self.tex.value(0.5, 0.5, &Point3::zero())
     }
 }
 
@@ -46,26 +211,80 @@ impl Material for Lambertian {
  * 
  * Uses a small sphere to randomize the reflected direction. The fuzziness param is the radius of the sphere.
  * Fuzz needs to be consistently scaled to the reflection vector so we need to normalize the reflected ray. 
+ *
+ * Directions are sampled as in the book (mirror direction plus a random point on the fuzz sphere), and the density
+ * of that is known exactly, so fuzzy metal can be light sampled too: eval = albedo * pdf, which keeps
+ * weight = albedo. Directions ending up below the surface are absorbed. With no fuzz it's a perfect mirror (specular).
  */
 pub struct Metal {
-    albedo: Color,
+    tex: Arc<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Self {
-        Self { albedo, fuzz: f64::min(fuzz, 1.0) }
+        Self::from_texture(albedo, fuzz)
+    }
+
+    pub fn from_texture(tex: impl IntoTexture, fuzz: f64) -> Self {
+        Self { tex: tex.into_texture(), fuzz: f64::min(fuzz, 1.0) }
+    }
+
+    /*
+     * Density of the unit direction towards a uniform random point on the fuzz sphere (radius fuzz, centered on
+     * the unit mirror direction). A ray along the direction enters and leaves the sphere at t = c +- s, with
+     * c = cos of the angle to the mirror direction and s = sqrt(c^2 - 1 + fuzz^2). Converting the area density
+     * 1 / (4 pi fuzz^2) at both points to solid angle (t^2 / cos, where cos = s / fuzz) and adding them up
+     * gives (c^2 + s^2) / (2 pi fuzz s). Directions outside the cone the sphere covers have no density.
+     */
+    fn fuzz_pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let reflected = reflect(r_in.direction(), rec.normal).unit_vector();
+        let direction = direction.unit_vector();
+        if self.fuzz <= 0.0 || direction.dot(&rec.normal) <= 0.0 {
+            return 0.0;
+        }
+
+        let c = direction.dot(&reflected);
+        let discriminant = c * c - 1.0 + self.fuzz * self.fuzz;
+        if c <= 0.0 || discriminant <= 0.0 {
+            return 0.0;
+        }
+        let s = discriminant.sqrt();
+        (c * c + s * s) / (2.0 * PI * self.fuzz * s)
     }
 }
 
 impl Material for Metal {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        scatter_from_sample(self.sample(r_in, rec), attenuation, scattered)
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
         let mut reflected = reflect(r_in.direction(), rec.normal);
         reflected = reflected.unit_vector() + self.fuzz * random_unit_vector(); // Add fuzz to the reflected ray
+        if reflected.dot(&rec.normal) <= 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            scattered: Ray::new(rec.p, reflected),
+            weight: self.tex.value_at(rec),
+            pdf: self.fuzz_pdf(r_in, rec, reflected),
+            is_specular: self.fuzz <= 0.0,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.tex.value_at(rec) * self.fuzz_pdf(r_in, rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        self.fuzz_pdf(r_in, rec, direction)
+    }
 
-        *scattered = Ray::new(rec.p, reflected);
-        *attenuation = self.albedo;
-        scattered.direction().dot(&rec.normal) > 0.0
+    // Fuzzed directions below the surface are absorbed, but that's shadowing, not the metal's color
+    fn albedo(&self, _r_in: &Ray, rec: &HitRecord) -> Color {
+        self.tex.value_at(rec)
     }
 }
 
@@ -73,14 +292,46 @@ impl Material for Metal {
  * Dielectric is like glass. It refracts light.
  * 
  * The refraction index is the ratio of the material's refractive index over the refractive index of the enclosing medium.
+ *
+ * Optionally the inside of the object absorbs light (Beer-Lambert law), see `with_absorption`.
+ *
+ * With a dispersion model the index of refraction depends on the wavelength, so in spectral mode white light
+ * splits into a rainbow (prisms, diamond fire). RGB rays use the index of refraction at 550nm.
  */
 pub struct Dielectric {
     refraction_index: f64,
+    absorption: Color, // Absorption coefficient per unit distance traveled inside, per channel
+    dispersion: Option<Dispersion>,
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f64) -> Self {
-        Self { refraction_index }
+        Self { refraction_index, absorption: Color::zero(), dispersion: None }
+    }
+
+    pub fn from_dispersion(dispersion: Dispersion) -> Self {
+        Self { dispersion: Some(dispersion), ..Self::new(dispersion.ior(550.0)) }
+    }
+
+    // Cauchy's equation n = a + b / lambda^2 (lambda in micrometers)
+    pub fn cauchy(a: f64, b: f64) -> Self {
+        Self::from_dispersion(Dispersion::Cauchy { a, b })
+    }
+
+    // Sellmeier equation n^2 = 1 + sum of b_i lambda^2 / (lambda^2 - c_i) (lambda in micrometers)
+    pub fn sellmeier(b: [f64; 3], c: [f64; 3]) -> Self {
+        Self::from_dispersion(Dispersion::Sellmeier { b, c })
+    }
+
+    // Light traveling a distance d inside keeps exp(-absorption * d) of its energy
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    // Absorption chosen so that light traveling `distance` inside ends up with the given color
+    pub fn with_transmission_color(self, color: Color, distance: f64) -> Self {
+        self.with_absorption(absorption_from_transmission(color, distance))
     }
 
     // Real glass has reflectivity that varies with angle of incidence.
@@ -94,8 +345,14 @@ impl Dielectric {
 
 impl Material for Dielectric {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
-        *attenuation = Color::new(1.0, 1.0, 1.0);
-        let r = if rec.front_face { 1.0 / self.refraction_index } else { self.refraction_index };
+        *attenuation = interior_transmittance(self.absorption, r_in, rec);
+
+        // Dispersion follows the hero wavelength only
+        let (refraction_index, wavelengths) = match (self.dispersion, r_in.wavelengths()) {
+            (Some(dispersion), Some(wavelengths)) => (dispersion.ior(wavelengths.hero()), Some(wavelengths.terminate_secondary())),
+            _ => (self.refraction_index, None),
+        };
+        let r = if rec.front_face { 1.0 / refraction_index } else { refraction_index };
 
         let unit_direction = r_in.direction().unit_vector();
         let cos_theta = f64::min(unit_direction.dot(&-rec.normal), 1.0);
@@ -110,6 +367,51 @@ impl Material for Dielectric {
         };
 
         *scattered = Ray::new(rec.p, direction);
+        if wavelengths.is_some() {
+            scattered.set_wavelengths(wavelengths);
+        }
         true
     }
-}
\ No newline at end of file
+
+    fn albedo(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        interior_transmittance(self.absorption, r_in, rec)
+    }
+}
+
+/*
+ * Beer-Lambert law: fraction of light left after traveling `distance` through a medium
+ * with the given absorption coefficient (per channel).
+ */
+pub fn beer_lambert(absorption: Color, distance: f64) -> Color {
+    Color::new(
+        f64::exp(-absorption.x() * distance),
+        f64::exp(-absorption.y() * distance),
+        f64::exp(-absorption.z() * distance),
+    )
+}
+
+/*
+ * Absorption coefficient that leaves exactly `color` after `distance`, i.e. -ln(color) / distance per channel.
+ * Easier to art direct than a raw coefficient ("this is the tint of a 1cm thick slab").
+ */
+pub fn absorption_from_transmission(color: Color, distance: f64) -> Color {
+    let coefficient = |c: f64| -f64::ln(c.clamp(1e-6, 1.0)) / distance;
+    Color::new(coefficient(color.x()), coefficient(color.y()), coefficient(color.z()))
+}
+
+/*
+ * Attenuation for a ray hitting the inside of an absorbing dielectric.
+ *
+ * A ray hitting a back face started at the previous interface event (entering or internally reflecting),
+ * so it traveled inside the object the whole way, for a distance of t * |direction|. Rays hitting a front
+ * face were outside and aren't attenuated.
+ *
+ * Note: This assumes nothing else sits inside the object. A ray that hits a nested object (e.g. an air bubble)
+ *       before reaching the back face isn't attenuated for that segment.
+ */
+pub fn interior_transmittance(absorption: Color, r_in: &Ray, rec: &HitRecord) -> Color {
+    if rec.front_face {
+        return Color::new(1.0, 1.0, 1.0);
+    }
+    beer_lambert(absorption, rec.t * r_in.direction().length())
+}
@@ -0,0 +1,108 @@
+/*
+ * Participating medium
+ *
+ * Stuff a ray can travel *through* that scatters and absorbs light along the way (the inside of wax, skin,
+ * milk or marble). A ray inside a medium carries it along (see Ray::medium), and the integrator asks the
+ * medium whether the ray scatters before reaching the next surface.
+ *
+ * - sigma_s: scattering coefficient (chance per unit distance of bouncing off a particle)
+ * - sigma_a: absorption coefficient (chance per unit distance of being absorbed)
+ * - sigma_t = sigma_s + sigma_a: extinction coefficient, 1 / sigma_t is the mean free path
+ *
+ * All coefficients are per color channel (or per sampled wavelength in spectral mode).
+ */
+
+use super::{color::Color, onb::Onb, ray::Ray, spectral::to_path_space, utils::{random_double, PI}, vec3::{Point3, Vec3}};
+
+#[derive(Clone, Copy)]
+pub struct Medium {
+    sigma_s: Color,
+    sigma_a: Color,
+    g: f64, // Henyey-Greenstein anisotropy: < 0 back scattering, 0 isotropic, > 0 forward scattering
+}
+
+// What happened to a ray traveling through a medium
+pub enum MediumEvent {
+    // Scattered off a particle at p, the ray continues in the medium with a new direction
+    Scatter { p: Point3, weight: Color },
+    // Made it to the end of the segment (the next surface hit), weighted by the transmittance
+    Pass { weight: Color },
+}
+
+impl Medium {
+    pub fn new(sigma_s: Color, sigma_a: Color, g: f64) -> Self {
+        Self { sigma_s, sigma_a, g: g.clamp(-0.99, 0.99) }
+    }
+
+    /*
+     * Medium from its single scattering albedo (sigma_s / sigma_t) and mean free path (1 / sigma_t) per channel,
+     * which are much easier to reason about than raw coefficients.
+     */
+    pub fn from_albedo(albedo: Color, mean_free_path: Color, g: f64) -> Self {
+        let coefficient = |mfp: f64| if mfp > 0.0 { 1.0 / mfp } else { 0.0 };
+        let sigma_t = Color::new(coefficient(mean_free_path.x()), coefficient(mean_free_path.y()), coefficient(mean_free_path.z()));
+        let sigma_s = albedo * sigma_t;
+        Self::new(sigma_s, sigma_t - sigma_s, g)
+    }
+
+    /*
+     * Samples a free-flight distance along r, up to parameter t_max (the next surface hit).
+     *
+     * The distance is sampled from the transmittance of one randomly picked channel, and the pdf is the average
+     * over channels (spectral MIS), so channels with very different coefficients don't blow up the weight.
+     */
+    pub fn sample(&self, r: &Ray, t_max: f64) -> MediumEvent {
+        let sigma_s = to_path_space(self.sigma_s, r);
+        let sigma_t = sigma_s + to_path_space(self.sigma_a, r);
+        let channels = [sigma_t.x(), sigma_t.y(), sigma_t.z()];
+
+        let speed = r.direction().length();
+        let max_distance = t_max * speed;
+
+        let channel_sigma_t = channels[usize::min((random_double() * 3.0) as usize, 2)];
+        let distance = if channel_sigma_t > 0.0 { -f64::ln(1.0 - random_double()) / channel_sigma_t } else { f64::INFINITY };
+        let transmittance = |d: f64| Color::new(f64::exp(-channels[0] * d), f64::exp(-channels[1] * d), f64::exp(-channels[2] * d));
+
+        if distance < max_distance {
+            let tr = transmittance(distance);
+            let pdf = (sigma_t * tr).average();
+            if pdf <= 0.0 {
+                return MediumEvent::Pass { weight: Color::zero() };
+            }
+            return MediumEvent::Scatter { p: r.at(distance / speed), weight: sigma_s * tr / pdf };
+        }
+
+        let tr = transmittance(max_distance);
+        let pdf = tr.average();
+        if pdf <= 0.0 {
+            return MediumEvent::Pass { weight: Color::zero() };
+        }
+        MediumEvent::Pass { weight: tr / pdf }
+    }
+
+    /*
+     * Samples a new direction from the Henyey-Greenstein phase function around the direction of travel.
+     * The phase function is sampled exactly, so the scattering weight is 1.
+     */
+    pub fn sample_phase(&self, direction: Vec3) -> Vec3 {
+        let g = self.g;
+        let xi = random_double();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            (1.0 + g * g - s * s) / (2.0 * g)
+        };
+        let sin_theta = f64::sqrt(f64::max(0.0, 1.0 - cos_theta * cos_theta));
+        let phi = 2.0 * PI * random_double();
+
+        Onb::new(direction).transform(Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta))
+    }
+
+    // Single scattering albedo (sigma_s / sigma_t) per channel, 1 for channels with nothing to scatter or absorb
+    pub fn albedo(&self) -> Color {
+        let sigma_t = self.sigma_s + self.sigma_a;
+        let ratio = |s: f64, t: f64| if t > 0.0 { s / t } else { 1.0 };
+        Color::new(ratio(self.sigma_s.x(), sigma_t.x()), ratio(self.sigma_s.y(), sigma_t.y()), ratio(self.sigma_s.z(), sigma_t.z()))
+    }
+}
@@ -0,0 +1,248 @@
+/*
+ * Microfacet theory
+ *
+ * A rough surface is modeled as lots of tiny perfect mirrors (microfacets) whose normals are spread around
+ * the macroscopic surface normal. Everything here works in a local shading frame where the surface normal
+ * is +z (see Onb).
+ *
+ * - D(wm): how many microfacets face direction wm (normal distribution function)
+ * - G(wo, wi): fraction of microfacets visible from both directions (masking-shadowing)
+ * - F: how much light each microfacet mirror reflects (Fresnel)
+ */
+
+use super::{color::Color, utils::PI, vec3::Vec3};
+
+/*
+ * GGX / Trowbridge-Reitz distribution with Smith masking-shadowing.
+ *
+ * alpha_x and alpha_y are the roughness along the local x (tangent) and y (bitangent) axes,
+ * equal values give an isotropic surface.
+ */
+#[derive(Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        Self { alpha_x, alpha_y }
+    }
+
+    /*
+     * Maps the perceptually linear "roughness" in [0, 1] artists use to alpha.
+     */
+    pub fn roughness_to_alpha(roughness: f64) -> f64 {
+        roughness * roughness
+    }
+
+    // Below this the surface is treated as a perfect mirror (or perfectly smooth glass)
+    pub fn effectively_smooth(&self) -> bool {
+        f64::max(self.alpha_x, self.alpha_y) < 1e-3
+    }
+
+    // Microfacet normal distribution D(wm)
+    pub fn d(&self, wm: Vec3) -> f64 {
+        if wm.z() <= 0.0 {
+            return 0.0;
+        }
+        let x = wm.x() / self.alpha_x;
+        let y = wm.y() / self.alpha_y;
+        let e = x * x + y * y + wm.z() * wm.z();
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    // Smith auxiliary function Lambda(w), which measures the invisible microfacet area per visible area
+    pub fn lambda(&self, w: Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let ax = self.alpha_x * w.x();
+        let ay = self.alpha_y * w.y();
+        let tan2_alpha2 = (ax * ax + ay * ay) / cos2;
+        0.5 * (-1.0 + f64::sqrt(1.0 + tan2_alpha2))
+    }
+
+    // Masking: fraction of microfacets (facing wm) visible from w
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Height-correlated masking-shadowing between wo and wi
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Distribution of normals visible from w
+    pub fn d_visible(&self, w: Vec3, wm: Vec3) -> f64 {
+        self.g1(w) / w.z().abs() * self.d(wm) * f64::abs(w.dot(&wm))
+    }
+
+    /*
+     * Samples a microfacet normal visible from w (Heitz 2018, "Sampling the GGX Distribution of Visible Normals").
+     *
+     * 1. Stretch w so the distribution becomes the isotropic alpha = 1 (hemisphere) case
+     * 2. Sample a point on the projected hemisphere as seen from w
+     * 3. Unstretch the resulting normal
+     *
+     * The pdf of the returned normal is d_visible(w, wm).
+     */
+    pub fn sample_wm(&self, w: Vec3, u1: f64, u2: f64) -> Vec3 {
+        // Work in the hemisphere w is in
+        let w = if w.z() < 0.0 { -w } else { w };
+        let vh = Vec3::new(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()).unit_vector();
+
+        // Orthonormal basis around vh
+        let lensq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if lensq > 0.0 { Vec3::new(-vh.y(), vh.x(), 0.0) / lensq.sqrt() } else { Vec3::new(1.0, 0.0, 0.0) };
+        let t2 = vh.cross(t1);
+
+        // Uniform point on the disk, warped onto the visible half of the projected hemisphere
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let mut p2 = r * phi.sin();
+        let s = 0.5 * (1.0 + vh.z());
+        p2 = (1.0 - s) * f64::sqrt(1.0 - p1 * p1) + s * p2;
+
+        // Reproject onto the hemisphere and unstretch
+        let nh = p1 * t1 + p2 * t2 + f64::sqrt(f64::max(0.0, 1.0 - p1 * p1 - p2 * p2)) * vh;
+        Vec3::new(self.alpha_x * nh.x(), self.alpha_y * nh.y(), f64::max(1e-6, nh.z())).unit_vector()
+    }
+
+    // D * G2 / (4 cos theta_o): the microfacet BRDF times cos theta_i, without the Fresnel term
+    pub fn reflection_eval(&self, wo: Vec3, wi: Vec3, wm: Vec3) -> f64 {
+        self.d(wm) * self.g(wo, wi) / (4.0 * wo.z())
+    }
+
+    // Density of reflecting wo about a sampled visible normal: d_visible times the Jacobian 1 / (4 |wo . wm|)
+    pub fn reflection_pdf(&self, wo: Vec3, wm: Vec3) -> f64 {
+        self.d_visible(wo, wm) / (4.0 * wo.dot(&wm).abs())
+    }
+}
+
+// Half vector of a reflection from wo to wi, None if either is below the surface or they're opposite
+pub fn reflection_half_vector(wo: Vec3, wi: Vec3) -> Option<Vec3> {
+    if wo.z() <= 0.0 || wi.z() <= 0.0 {
+        return None;
+    }
+    let wm = wo + wi;
+    if wm.near_zero() { None } else { Some(wm.unit_vector()) }
+}
+
+/*
+ * Rough dielectric BSDF (Walter et al. 2007) for wo above the surface, with eta the relative index of
+ * refraction across it (transmitted side over the side of wo).
+ *
+ * Returns the BSDF times |cos theta_i| and the density of sampling wi by picking a visible normal,
+ * then reflecting off it with its Fresnel reflectance or refracting through it otherwise. Refraction
+ * uses the generalized half vector wo + eta * wi.
+ *
+ * Note: Radiance isn't scaled by 1 / eta^2 when crossing the interface, matching the G2 / G1 weight of
+ *       the sampled directions.
+ */
+pub fn rough_dielectric(distribution: &TrowbridgeReitz, wo: Vec3, wi: Vec3, eta: f64) -> (f64, f64) {
+    if wo.z() <= 0.0 || wi.z() == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    if wi.z() > 0.0 {
+        let Some(wm) = reflection_half_vector(wo, wi) else {
+            return (0.0, 0.0);
+        };
+        let reflectance = fresnel_dielectric(wo.dot(&wm), eta);
+        return (reflectance * distribution.reflection_eval(wo, wi, wm), reflectance * distribution.reflection_pdf(wo, wm));
+    }
+
+    let wm = wo + eta * wi;
+    if wm.near_zero() {
+        return (0.0, 0.0);
+    }
+    let wm = if wm.z() < 0.0 { -wm.unit_vector() } else { wm.unit_vector() };
+
+    // Microfacets facing away from either direction can't refract between them
+    let cos_i = wi.dot(&wm);
+    let cos_o = wo.dot(&wm);
+    if cos_i >= 0.0 || cos_o <= 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let transmittance = 1.0 - fresnel_dielectric(cos_o, eta);
+    let denom = (cos_i + cos_o / eta) * (cos_i + cos_o / eta);
+    let f = transmittance * distribution.d(wm) * distribution.g(wo, wi) * f64::abs(cos_i * cos_o) / (wo.z() * denom);
+    let pdf = transmittance * distribution.d_visible(wo, wm) * cos_i.abs() / denom;
+    (f, pdf)
+}
+
+/*
+ * Exact Fresnel reflectance of a conductor (complex index of refraction eta + i k) for unpolarized light,
+ * evaluated per color channel.
+ */
+pub fn fresnel_conductor(cos_theta_i: f64, eta: Color, k: Color) -> Color {
+    let cos_theta_i = cos_theta_i.clamp(0.0, 1.0);
+    let per_channel = |eta: f64, k: f64| {
+        let cos2 = cos_theta_i * cos_theta_i;
+        let sin2 = 1.0 - cos2;
+        let eta2 = eta * eta;
+        let k2 = k * k;
+
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = f64::sqrt(f64::max(0.0, t0 * t0 + 4.0 * eta2 * k2));
+        let t1 = a2_plus_b2 + cos2;
+        let a = f64::sqrt(f64::max(0.0, 0.5 * (a2_plus_b2 + t0)));
+        let t2 = 2.0 * cos_theta_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        0.5 * (rp + rs)
+    };
+
+    Color::new(
+        per_channel(eta.x(), k.x()),
+        per_channel(eta.y(), k.y()),
+        per_channel(eta.z(), k.z()),
+    )
+}
+
+/*
+ * Exact Fresnel reflectance of a dielectric interface for unpolarized light.
+ *
+ * eta is the relative index of refraction (transmitted side over incident side) for light arriving from
+ * the side the normal points to (cos_theta_i > 0). Returns 1 on total internal reflection.
+ */
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let mut cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let mut eta = eta;
+    if cos_theta_i < 0.0 {
+        // Arriving from the other side of the interface
+        eta = 1.0 / eta;
+        cos_theta_i = -cos_theta_i;
+    }
+
+    let sin2_theta_i = 1.0 - cos_theta_i * cos_theta_i;
+    let sin2_theta_t = sin2_theta_i / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = f64::sqrt(1.0 - sin2_theta_t);
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+/*
+ * Schlick's approximation of Fresnel reflectance, from the reflectance f0 at normal incidence.
+ */
+pub fn fresnel_schlick(f0: Color, cos_theta: f64) -> Color {
+    f0 + (Color::new(1.0, 1.0, 1.0) - f0) * schlick_weight(cos_theta)
+}
+
+// The (1 - cos)^5 falloff used by Schlick-style terms
+pub fn schlick_weight(cos_theta: f64) -> f64 {
+    f64::powi(1.0 - cos_theta.clamp(0.0, 1.0), 5)
+}
@@ -0,0 +1,195 @@
+/*
+ * Normal and bump mapping
+ *
+ * Both wrap another material and only change the shading normal the wrapped material sees,
+ * adding fine surface detail (grooves, dents, stucco) without adding geometry.
+ *
+ * They rely on the tangent frame in the hit record: tangent = direction of increasing u,
+ * bitangent = direction of increasing v, normal = surface normal.
+ */
+
+use std::sync::Arc;
+
+use super::{color::Color, hittable::HitRecord, material::{BsdfSample, Material}, ray::Ray, texture::{IntoTexture, Texture}, vec3::Vec3};
+
+/*
+ * Normal mapping: a texture stores the shading normal in tangent space, with each channel remapped
+ * from [-1, 1] to [0, 1] (the usual "blue-ish" normal map images, +z pointing away from the surface).
+ *
+ * Normal map images store data, not colors, so load them with `ImageTexture::load_linear`.
+ */
+pub struct NormalMapped {
+    base: Arc<dyn Material>,
+    normal_map: Arc<dyn Texture>,
+    strength: f64, // Scales the tangent-space x/y tilt, 1 uses the map as-is
+}
+
+impl NormalMapped {
+    pub fn new(base: Arc<dyn Material>, normal_map: impl IntoTexture, strength: f64) -> Self {
+        Self { base, normal_map: normal_map.into_texture(), strength }
+    }
+
+    fn shading(&self, rec: &HitRecord) -> HitRecord {
+        let c = self.normal_map.value_at(rec);
+        let n = 2.0 * c - Vec3::new(1.0, 1.0, 1.0);
+
+        let mut shading = rec.clone();
+        shading.normal = perturb(rec, self.strength * n.x(), self.strength * n.y(), n.z());
+        shading
+    }
+}
+
+impl Material for NormalMapped {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        self.base.scatter(r_in, &self.shading(rec), attenuation, scattered)
+    }
+
+    fn is_opaque_at(&self, rec: &HitRecord) -> bool {
+        self.base.is_opaque_at(rec)
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        self.base.sample(r_in, &self.shading(rec))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.base.eval(r_in, &self.shading(rec), direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        self.base.pdf(r_in, &self.shading(rec), direction)
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.base.emitted(rec)
+    }
+
+    fn average_emitted(&self) -> Color {
+        self.base.average_emitted()
+    }
+
+    fn albedo(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        self.base.albedo(r_in, &self.shading(rec))
+    }
+}
+
+/*
+ * Bump mapping: a scalar height texture (the red channel is used) displaces the surface along its normal.
+ * The normal is tilted against the height gradient, which is estimated with finite differences in (u, v).
+ *
+ * Note: The same small offset is applied to (u, v) and to p along the tangent frame, so both
+ *       image (u, v) textures and solid procedural textures can be used as height maps.
+ *       All three height samples share the hit's footprint, so mipmapped textures are differentiated
+ *       within a single level of detail.
+ */
+pub struct BumpMapped {
+    base: Arc<dyn Material>,
+    height: Arc<dyn Texture>,
+    strength: f64, // Bump height scale
+}
+
+impl BumpMapped {
+    const DELTA: f64 = 1e-4;
+
+    pub fn new(base: Arc<dyn Material>, height: impl IntoTexture, strength: f64) -> Self {
+        Self { base, height: height.into_texture(), strength }
+    }
+
+    // The hit moved by (du, dv) along the tangent frame, keeping its footprint so filtered textures read the same mip level
+    fn offset(rec: &HitRecord, du: f64, dv: f64) -> HitRecord {
+        let mut offset = rec.clone();
+        offset.u += du;
+        offset.v += dv;
+        offset.p += du * rec.tangent + dv * rec.bitangent;
+        offset
+    }
+
+    fn shading(&self, rec: &HitRecord) -> HitRecord {
+        let d = Self::DELTA;
+        let h = self.height.value_at(rec).x();
+        let h_u = self.height.value_at(&Self::offset(rec, d, 0.0)).x();
+        let h_v = self.height.value_at(&Self::offset(rec, 0.0, d)).x();
+
+        let dh_du = self.strength * (h_u - h) / d;
+        let dh_dv = self.strength * (h_v - h) / d;
+
+        let mut shading = rec.clone();
+        shading.normal = perturb(rec, -dh_du, -dh_dv, 1.0);
+        shading
+    }
+}
+
+impl Material for BumpMapped {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        self.base.scatter(r_in, &self.shading(rec), attenuation, scattered)
+    }
+
+    fn is_opaque_at(&self, rec: &HitRecord) -> bool {
+        self.base.is_opaque_at(rec)
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        self.base.sample(r_in, &self.shading(rec))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.base.eval(r_in, &self.shading(rec), direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        self.base.pdf(r_in, &self.shading(rec), direction)
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.base.emitted(rec)
+    }
+
+    fn average_emitted(&self) -> Color {
+        self.base.average_emitted()
+    }
+
+    fn albedo(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        self.base.albedo(r_in, &self.shading(rec))
+    }
+}
+
+// Transforms a tangent-space direction (x along tangent, y along bitangent, z along normal) into a unit world normal
+fn perturb(rec: &HitRecord, x: f64, y: f64, z: f64) -> Vec3 {
+    let n = x * rec.tangent + y * rec.bitangent + z * rec.normal;
+    if n.near_zero() { rec.normal } else { n.unit_vector() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::{image_texture::{FilterMode, ImageTexture}, material::Lambertian};
+
+    // Trilinear height map rising linearly along u, plus 2 texel tall stripes along v that average out
+    // to a constant offset from the 4th mip level on (so coarser levels hold different heights)
+    fn striped_ramp(size: usize) -> ImageTexture {
+        let height = |i: usize, j: usize| i as f64 / size as f64 + if (j / 2).is_multiple_of(2) { 0.5 } else { 0.0 };
+        let pixels = (0..size * size).map(|k| Color::new(height(k % size, k / size), 0.0, 0.0)).collect();
+        ImageTexture::from_pixels(size, size, pixels).with_filter(FilterMode::Trilinear)
+    }
+
+    #[test]
+    fn bump_gradient_ignores_the_mip_level() {
+        let bumped = BumpMapped::new(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))), Arc::new(striped_ramp(64)), 0.5);
+        let mut rec = HitRecord {
+            u: 0.5,
+            v: 32.5 / 64.0, // Texel row center, the stripe is flat around it
+            normal: Vec3::new(0.0, 0.0, 1.0),
+            tangent: Vec3::new(1.0, 0.0, 0.0),
+            bitangent: Vec3::new(0.0, 1.0, 0.0),
+            ..HitRecord::default()
+        };
+        let level0 = bumped.shading(&rec).normal;
+
+        // A footprint of a quarter texture selects the 4x4 texel mip level
+        rec.uv_footprint = 0.25;
+        let wide = bumped.shading(&rec).normal;
+
+        assert!((wide - level0).length() < 1e-6);
+        assert!(level0.x() < -0.1, "the ramp should tilt the normal against u");
+    }
+}
@@ -0,0 +1,51 @@
+/*
+ * Orthonormal basis
+ *
+ * Three mutually perpendicular unit vectors (u, v, w). Used to build a local "shading frame" at a hit point
+ * with w along the surface normal, so directions can be sampled in a simple local space (where the normal
+ * is +z) and then transformed back into world space.
+ */
+
+use super::vec3::Vec3;
+
+#[derive(Clone, Copy)]
+pub struct Onb {
+    axis: [Vec3; 3],
+}
+
+impl Onb {
+    // Basis around a normal n, with an arbitrary (but consistent) choice of u and v
+    pub fn new(n: Vec3) -> Self {
+        let w = n.unit_vector();
+        let a = if w.x().abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let v = w.cross(a).unit_vector();
+        let u = w.cross(v);
+        Self { axis: [u, v, w] }
+    }
+
+    // Basis around a normal n with u following the tangent t as closely as possible (Gram-Schmidt)
+    pub fn from_normal_tangent(n: Vec3, t: Vec3) -> Self {
+        let w = n.unit_vector();
+        let t = t - t.dot(&w) * w;
+        if t.near_zero() {
+            return Self::new(n);
+        }
+        let u = t.unit_vector();
+        let v = w.cross(u);
+        Self { axis: [u, v, w] }
+    }
+
+    pub fn u(&self) -> Vec3 { self.axis[0] }
+    pub fn v(&self) -> Vec3 { self.axis[1] }
+    pub fn w(&self) -> Vec3 { self.axis[2] }
+
+    // Local coordinates (relative to this basis) to world space
+    pub fn transform(&self, v: Vec3) -> Vec3 {
+        v.x() * self.axis[0] + v.y() * self.axis[1] + v.z() * self.axis[2]
+    }
+
+    // World space to local coordinates (relative to this basis)
+    pub fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(v.dot(&self.axis[0]), v.dot(&self.axis[1]), v.dot(&self.axis[2]))
+    }
+}
@@ -0,0 +1,117 @@
+/*
+ * Perlin noise
+ *
+ * Smooth, repeatable "random" values over 3D space. Every integer lattice point gets a random unit gradient
+ * vector (picked by hashing its coordinates through random permutation tables), and the noise at a point
+ * is the trilinear blend of the dot products between each surrounding gradient and the offset to that corner.
+ *
+ * The blend weights are Hermite smoothed (3t^2 - 2t^3) so there are no visible grid lines at cell borders.
+ * Output is roughly in [-1, 1].
+ */
+
+use super::{utils::random_double, vec3::{random_in_range, Point3, Vec3}};
+
+const POINT_COUNT: usize = 256;
+
+pub struct Perlin {
+    randvec: Vec<Vec3>, // Random unit gradient per hashed lattice point
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Perlin {
+    pub fn new() -> Self {
+        let randvec = (0..POINT_COUNT).map(|_| random_in_range(-1.0, 1.0).unit_vector()).collect();
+
+        Self {
+            randvec,
+            perm_x: Self::generate_perm(),
+            perm_y: Self::generate_perm(),
+            perm_z: Self::generate_perm(),
+        }
+    }
+
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let u = p.x() - p.x().floor();
+        let v = p.y() - p.y().floor();
+        let w = p.z() - p.z().floor();
+
+        let i = p.x().floor() as i64;
+        let j = p.y().floor() as i64;
+        let k = p.z().floor() as i64;
+
+        // Gradients at the 8 corners of the lattice cell containing p
+        let mut c = [[[Vec3::zero(); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    *corner = self.randvec[
+                        self.perm_x[((i + di as i64) & 255) as usize] ^
+                        self.perm_y[((j + dj as i64) & 255) as usize] ^
+                        self.perm_z[((k + dk as i64) & 255) as usize]
+                    ];
+                }
+            }
+        }
+
+        Self::perlin_interp(&c, u, v, w)
+    }
+
+    /*
+     * Turbulence (fractional Brownian motion): sum of noise octaves, each at double the frequency
+     * and half the amplitude of the previous one.
+     */
+    pub fn turb(&self, p: &Point3, depth: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p *= 2.0;
+        }
+
+        accum.abs()
+    }
+
+    // Random permutation of 0..POINT_COUNT (Fisher-Yates shuffle)
+    fn generate_perm() -> Vec<usize> {
+        let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+        for i in (1..POINT_COUNT).rev() {
+            let target = (random_double() * (i + 1) as f64) as usize;
+            p.swap(i, usize::min(target, i));
+        }
+        p
+    }
+
+    // Trilinear interpolation of the corner gradient contributions with Hermite smoothing
+    fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+        let mut accum = 0.0;
+
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, corner) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight_v = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * corner.dot(&weight_v);
+                }
+            }
+        }
+
+        accum
+    }
+}
@@ -0,0 +1,347 @@
+/*
+ * Principled (Disney-style "uber") material
+ *
+ * One material whose parameters match what Blender's Principled BSDF / Substance / glTF metallic-roughness
+ * expose, so looks can be moved over directly. Every parameter is a texture (see IntoTexture), scalar
+ * parameters read the red channel and are expected in [0, 1] (except ior).
+ *
+ * The surface is a stack of layers, each a lobe of the BSDF with a weight:
+ *
+ * 1. Clearcoat: a thin glossy varnish (fixed IOR 1.5) on top of everything, weighted by its Fresnel reflectance
+ * 2. Metallic: GGX reflection tinted by the base color (Schlick Fresnel with f0 = base color)
+ * 3. Transmission: rough glass (GGX reflection and refraction) tinted by the base color
+ * 4. Otherwise opaque dielectric: GGX specular reflection with Schlick Fresnel, and the light it doesn't
+ *    reflect (at the surface normal) goes to Disney diffuse (with retro-reflection at grazing angles) plus sheen
+ *
+ * Each sample picks a single lobe with the probability of its weight. Perfectly smooth lobes are mirrors (or
+ * smooth glass) and are sampled as specular, so their weight is just the remaining BSDF factor, which for GGX
+ * lobes sampled from the visible normals is F * G2 / G1. Directions from the other lobes are weighted by the
+ * BSDF of all of them (`eval` / `pdf`), so light sampling sees the same material as scattered rays.
+ */
+
+use std::sync::Arc;
+
+use super::{color::Color, hittable::HitRecord, material::{scatter_from_sample, BsdfSample, Material}, microfacet::{fresnel_dielectric, fresnel_schlick, reflection_half_vector, rough_dielectric, schlick_weight, TrowbridgeReitz}, onb::Onb, ray::Ray, texture::{IntoTexture, Texture}, utils::{random_double, PI}, vec3::{random_cosine_direction, reflect, refract, Vec3}};
+
+pub struct Principled {
+    base_color: Arc<dyn Texture>,
+    metallic: Arc<dyn Texture>, // 0 = dielectric, 1 = metal
+    roughness: Arc<dyn Texture>, // Roughness of the specular, metallic and transmission lobes
+    specular: Arc<dyn Texture>, // Dielectric specular amount, 0.5 = 4% reflectance at normal incidence
+    specular_tint: Arc<dyn Texture>, // Tints the dielectric specular towards the base color
+    sheen: Arc<dyn Texture>, // Extra grazing-angle reflection for cloth
+    sheen_tint: Arc<dyn Texture>, // Tints the sheen towards the base color
+    clearcoat: Arc<dyn Texture>, // Strength of the clearcoat layer
+    clearcoat_gloss: Arc<dyn Texture>, // 0 = satin, 1 = gloss clearcoat
+    transmission: Arc<dyn Texture>, // 0 = opaque, 1 = fully transmissive (glass)
+    ior: Arc<dyn Texture>, // Index of refraction used by transmission
+}
+
+impl Principled {
+    pub fn new(base_color: impl IntoTexture) -> Self {
+        Self {
+            base_color: base_color.into_texture(),
+            metallic: 0.0.into_texture(),
+            roughness: 0.5.into_texture(),
+            specular: 0.5.into_texture(),
+            specular_tint: 0.0.into_texture(),
+            sheen: 0.0.into_texture(),
+            sheen_tint: 0.5.into_texture(),
+            clearcoat: 0.0.into_texture(),
+            clearcoat_gloss: 1.0.into_texture(),
+            transmission: 0.0.into_texture(),
+            ior: 1.45.into_texture(),
+        }
+    }
+
+    pub fn with_metallic(mut self, metallic: impl IntoTexture) -> Self {
+        self.metallic = metallic.into_texture();
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: impl IntoTexture) -> Self {
+        self.roughness = roughness.into_texture();
+        self
+    }
+
+    pub fn with_specular(mut self, specular: impl IntoTexture) -> Self {
+        self.specular = specular.into_texture();
+        self
+    }
+
+    pub fn with_specular_tint(mut self, specular_tint: impl IntoTexture) -> Self {
+        self.specular_tint = specular_tint.into_texture();
+        self
+    }
+
+    pub fn with_sheen(mut self, sheen: impl IntoTexture) -> Self {
+        self.sheen = sheen.into_texture();
+        self
+    }
+
+    pub fn with_sheen_tint(mut self, sheen_tint: impl IntoTexture) -> Self {
+        self.sheen_tint = sheen_tint.into_texture();
+        self
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat: impl IntoTexture) -> Self {
+        self.clearcoat = clearcoat.into_texture();
+        self
+    }
+
+    pub fn with_clearcoat_gloss(mut self, clearcoat_gloss: impl IntoTexture) -> Self {
+        self.clearcoat_gloss = clearcoat_gloss.into_texture();
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: impl IntoTexture) -> Self {
+        self.transmission = transmission.into_texture();
+        self
+    }
+
+    pub fn with_ior(mut self, ior: impl IntoTexture) -> Self {
+        self.ior = ior.into_texture();
+        self
+    }
+}
+
+// The lobes at a hit, in the local shading frame
+struct Lobes {
+    frame: Onb,
+    wo: Vec3,
+    front_face: bool,
+    base_color: Color,
+    roughness: f64,
+    distribution: TrowbridgeReitz, // Metallic, transmission and specular lobes
+    coat: TrowbridgeReitz,
+    eta: f64, // Relative index of refraction for transmission, as seen from the incoming side
+    f0: f64, // Dielectric specular reflectance at normal incidence
+    specular_color: Color,
+    sheen_color: Color, // Sheen color times the sheen amount
+
+    // Lobe weights, also the probabilities of sampling them (they add up to 1)
+    clearcoat: f64,
+    metallic: f64,
+    transmission: f64,
+    opaque: f64, // Shared by specular (with probability `specular`) and diffuse
+    specular: f64, // Schlick Fresnel reflectance at the surface normal
+}
+
+impl Principled {
+    // None for rays arriving from below the shading normal
+    fn lobes(&self, r_in: &Ray, rec: &HitRecord) -> Option<Lobes> {
+        let scalar = |tex: &Arc<dyn Texture>| tex.value_at(rec).x().clamp(0.0, 1.0);
+        let base_color = self.base_color.value_at(rec);
+        let roughness = scalar(&self.roughness);
+
+        let frame = Onb::from_normal_tangent(rec.normal, rec.tangent);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let alpha = f64::max(TrowbridgeReitz::roughness_to_alpha(roughness), 1e-4);
+        let gloss = scalar(&self.clearcoat_gloss);
+        let coat_alpha = (1.0 - gloss) * 0.1 + gloss * 0.001;
+
+        // Hue and saturation of the base color without its luminance, for the tint parameters
+        let luminance = 0.3 * base_color.x() + 0.6 * base_color.y() + 0.1 * base_color.z();
+        let tint = if luminance > 0.0 { base_color / luminance } else { Color::new(1.0, 1.0, 1.0) };
+        let white = Color::new(1.0, 1.0, 1.0);
+        let specular_tint = scalar(&self.specular_tint);
+        let sheen_tint = scalar(&self.sheen_tint);
+
+        let ior = f64::max(self.ior.value_at(rec).x(), 1.0);
+        let f0 = 0.08 * scalar(&self.specular);
+
+        // The clearcoat is only on the outside
+        let clearcoat = if rec.front_face { scalar(&self.clearcoat) * fresnel_dielectric(wo.z(), 1.5) } else { 0.0 };
+        let metallic = (1.0 - clearcoat) * scalar(&self.metallic);
+        let transmission = (1.0 - clearcoat - metallic) * scalar(&self.transmission);
+
+        Some(Lobes {
+            frame,
+            wo,
+            front_face: rec.front_face,
+            base_color,
+            roughness,
+            distribution: TrowbridgeReitz::new(alpha, alpha),
+            coat: TrowbridgeReitz::new(coat_alpha, coat_alpha),
+            eta: if rec.front_face { ior } else { 1.0 / ior },
+            f0,
+            specular_color: (1.0 - specular_tint) * white + specular_tint * tint,
+            sheen_color: scalar(&self.sheen) * ((1.0 - sheen_tint) * white + sheen_tint * tint),
+            clearcoat,
+            metallic,
+            transmission,
+            opaque: 1.0 - clearcoat - metallic - transmission,
+            specular: fresnel_schlick(Color::new(f0, f0, f0), wo.z()).x(),
+        })
+    }
+}
+
+impl Lobes {
+    // Sum of all lobes except perfectly smooth ones: BSDF times cos theta_i, with wi in the local frame
+    fn eval(&self, wi: Vec3) -> Color {
+        let wo = self.wo;
+        let mut f = Color::zero();
+
+        if let Some(wm) = reflection_half_vector(wo, wi) {
+            if !self.coat.effectively_smooth() {
+                f += self.clearcoat * self.coat.reflection_eval(wo, wi, wm) * Color::new(1.0, 1.0, 1.0);
+            }
+            if !self.distribution.effectively_smooth() {
+                let reflection = self.distribution.reflection_eval(wo, wi, wm);
+                f += self.metallic * reflection * fresnel_schlick(self.base_color, wo.dot(&wm));
+
+                let specular = fresnel_schlick(Color::new(self.f0, self.f0, self.f0), wo.dot(&wm)).x();
+                f += self.opaque * specular * reflection * self.specular_color;
+            }
+        }
+
+        if !self.distribution.effectively_smooth() {
+            let (transmission, _) = rough_dielectric(&self.distribution, wo, wi, self.eta);
+            f += self.transmission * transmission * self.transmission_color(wi);
+        }
+
+        if wi.z() > 0.0 {
+            f += (self.opaque * (1.0 - self.specular) * wi.z() / PI) * self.diffuse(wi);
+        }
+        f
+    }
+
+    // Density of sampling wi (local frame) with `sample`, leaving out perfectly smooth lobes
+    fn pdf(&self, wi: Vec3) -> f64 {
+        let wo = self.wo;
+        let mut pdf = 0.0;
+
+        if let Some(wm) = reflection_half_vector(wo, wi) {
+            if !self.coat.effectively_smooth() {
+                pdf += self.clearcoat * self.coat.reflection_pdf(wo, wm);
+            }
+            if !self.distribution.effectively_smooth() {
+                pdf += (self.metallic + self.opaque * self.specular) * self.distribution.reflection_pdf(wo, wm);
+            }
+        }
+
+        if !self.distribution.effectively_smooth() {
+            pdf += self.transmission * rough_dielectric(&self.distribution, wo, wi, self.eta).1;
+        }
+
+        if wi.z() > 0.0 {
+            pdf += self.opaque * (1.0 - self.specular) * wi.z() / PI;
+        }
+        pdf
+    }
+
+    // Tinted once, when refracting into the object
+    fn transmission_color(&self, wi: Vec3) -> Color {
+        if self.front_face && wi.z() < 0.0 { self.base_color } else { Color::new(1.0, 1.0, 1.0) }
+    }
+
+    // Disney diffuse plus sheen, times pi (the weight of a cosine sampled direction)
+    fn diffuse(&self, wi: Vec3) -> Color {
+        let wo = self.wo;
+        let h = wo + wi;
+        let cos_d = if h.near_zero() { 1.0 } else { wi.dot(&h.unit_vector()) };
+
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let fd = (1.0 + (fd90 - 1.0) * schlick_weight(wi.z())) * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z()));
+        let sheen = schlick_weight(cos_d) * PI * self.sheen_color;
+
+        fd * self.base_color + sheen
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        scatter_from_sample(self.sample(r_in, rec), attenuation, scattered)
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let lobes = self.lobes(r_in, rec)?;
+        let wo = lobes.wo;
+        let specular = |wi: Vec3, weight: Color| {
+            Some(BsdfSample { scattered: Ray::new(rec.p, lobes.frame.transform(wi)), weight, pdf: 0.0, is_specular: true })
+        };
+
+        // Pick a lobe with the probability of its weight
+        let u = random_double();
+        let metallic_end = lobes.clearcoat + lobes.metallic;
+        let transmission_end = metallic_end + lobes.transmission;
+        let specular_end = transmission_end + lobes.opaque * lobes.specular;
+
+        let wi = if u < lobes.clearcoat {
+            let (wi, _) = sample_reflection(&lobes.coat, wo)?;
+            if lobes.coat.effectively_smooth() {
+                return specular(wi, Color::new(1.0, 1.0, 1.0) * (lobes.coat.g(wo, wi) / lobes.coat.g1(wo)));
+            }
+            wi
+        } else if u < metallic_end {
+            let (wi, wm) = sample_reflection(&lobes.distribution, wo)?;
+            if lobes.distribution.effectively_smooth() {
+                let weight = fresnel_schlick(lobes.base_color, wo.dot(&wm)) * (lobes.distribution.g(wo, wi) / lobes.distribution.g1(wo));
+                return specular(wi, weight);
+            }
+            wi
+        } else if u < transmission_end {
+            // Rough glass: reflect or refract through a visible microfacet, picked with its Fresnel reflectance
+            let wm = lobes.distribution.sample_wm(wo, random_double(), random_double());
+            let reflected = random_double() < fresnel_dielectric(wo.dot(&wm), lobes.eta);
+            let wi = if reflected { reflect(-wo, wm) } else { refract(-wo, wm, 1.0 / lobes.eta) };
+            if reflected != (wi.z() > 0.0) {
+                // Reflected into the surface or refracted back to the incoming side
+                return None;
+            }
+            if lobes.distribution.effectively_smooth() {
+                let weight = lobes.transmission_color(wi) * (lobes.distribution.g(wo, wi) / lobes.distribution.g1(wo));
+                return specular(wi, weight);
+            }
+            wi
+        } else if u < specular_end {
+            let (wi, _) = sample_reflection(&lobes.distribution, wo)?;
+            if lobes.distribution.effectively_smooth() {
+                return specular(wi, lobes.specular_color * (lobes.distribution.g(wo, wi) / lobes.distribution.g1(wo)));
+            }
+            wi
+        } else {
+            random_cosine_direction()
+        };
+
+        let pdf = lobes.pdf(wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            scattered: Ray::new(rec.p, lobes.frame.transform(wi)),
+            weight: lobes.eval(wi) / pdf,
+            pdf,
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        match self.lobes(r_in, rec) {
+            Some(lobes) => lobes.eval(lobes.frame.to_local(direction.unit_vector())),
+            None => Color::zero(),
+        }
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        match self.lobes(r_in, rec) {
+            Some(lobes) => lobes.pdf(lobes.frame.to_local(direction.unit_vector())),
+            None => 0.0,
+        }
+    }
+
+    fn albedo(&self, _r_in: &Ray, rec: &HitRecord) -> Color {
+        self.base_color.value_at(rec)
+    }
+}
+
+// Mirrors wo about a sampled visible microfacet normal, None if it ends up below the surface
+fn sample_reflection(distribution: &TrowbridgeReitz, wo: Vec3) -> Option<(Vec3, Vec3)> {
+    let wm = distribution.sample_wm(wo, random_double(), random_double());
+    let wi = reflect(-wo, wm);
+    if wi.z() <= 0.0 { None } else { Some((wi, wm)) }
+}
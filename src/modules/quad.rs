@@ -0,0 +1,115 @@
+/*
+ * Quad
+ *
+ * A flat parallelogram defined by a corner Q and two edge vectors u and v:
+ * the points Q + a * u + b * v for a, b in [0, 1].
+ *
+ * (a, b) are also its texture coordinates, so an image maps onto the quad exactly once,
+ * with (0, 0) at Q. The front face is the side the normal u x v points to.
+ */
+
+use std::sync::Arc;
+
+use super::{color::luminance, hittable::{HitRecord, Hittable}, interval::Interval, light::LightBounds, material::Material, ray::Ray, utils::{random_double, INFINITY, PI}, vec3::{Point3, Vec3}};
+
+pub struct Quad {
+    q: Point3, // Starting corner
+    u: Vec3, // First edge
+    v: Vec3, // Second edge
+    w: Vec3, // n / (n . n), used to find the planar coordinates of a hit
+    mat: Arc<dyn Material>,
+    normal: Vec3, // Unit normal of the plane containing the quad
+    d: f64, // Plane equation: normal . p = d
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material>) -> Self {
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+        let d = normal.dot(&q);
+        let w = n / n.dot(&n);
+        let area = n.length();
+
+        Self { q, u, v, w, mat, normal, d, area }
+    }
+}
+
+impl Hittable for Quad {
+    /*
+     * Ray-quad intersection
+     *
+     * 1. Intersect the ray with the plane containing the quad
+     * 2. Express the hit point in the quad's (u, v) frame: p = Q + alpha * u + beta * v
+     * 3. The point is inside the quad if both alpha and beta are in [0, 1]
+     */
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let denom = self.normal.dot(&r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = (self.d - self.normal.dot(&r.origin())) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        let intersection = r.at(t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = self.w.dot(&planar_hitpt_vector.cross(self.v));
+        let beta = self.w.dot(&self.u.cross(planar_hitpt_vector));
+
+        let unit_interval = Interval::new(0.0, 1.0);
+        if !unit_interval.contains(alpha) || !unit_interval.contains(beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = intersection;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.set_face_normal(r, self.normal);
+        rec.set_tangent_frame(self.normal, self.u);
+        // Lines of constant u are one unit apart at the quad's height over v, |u x v| / |v| (and likewise for v)
+        rec.set_uv_footprint(r, self.area / self.v.length(), self.area / self.u.length());
+
+        // Cut out parts of the quad aren't hit
+        if !self.mat.is_opaque_at(rec) {
+            return false;
+        }
+        rec.mat = self.mat.clone();
+
+        true
+    }
+
+    /*
+     * Points are sampled uniformly over the area (pdf 1 / area), converted to a solid angle density
+     * from the origin: distance^2 / (|cos theta| * area), where theta is the angle to the quad's normal.
+     */
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        if !self.hit(&Ray::new(origin, direction), Interval::new(0.001, INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = f64::abs(direction.dot(&rec.normal) / direction.length());
+
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let p = self.q + (random_double() * self.u) + (random_double() * self.v);
+        p - origin
+    }
+
+    // A diffuse emitter sends out pi * radiance per unit area (from the front face only)
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let center = self.q + 0.5 * (self.u + self.v);
+        let radius = 0.5 * f64::max((self.u + self.v).length(), (self.u - self.v).length());
+        let power = PI * self.area * luminance(self.mat.average_emitted());
+        Some(LightBounds { center, radius, power })
+    }
+}
@@ -9,23 +9,62 @@
 
 use crate::modules::vec3::Vec3;
 
+use super::{medium::Medium, spectral::SampledWavelengths};
+
 pub struct Ray {
     origin: Vec3,
-    direction: Vec3
+    direction: Vec3,
+    wavelengths: Option<SampledWavelengths>, // Wavelengths carried in spectral mode, None for RGB rays
+    medium: Option<Medium>, // Medium the ray is traveling through, None for empty space
+    cone: Option<RayCone>, // Footprint of the pixel the ray belongs to, None if unknown
 }
 
-impl Ray {
-    pub fn default() -> Self {
-        Self { origin: Vec3::new(0.0, 0.0, 0.0), direction: Vec3::new(0.0, 0.0, 0.0) }
+/*
+ * Ray cone: how wide the area seen through one pixel is along a ray, used to filter textures
+ * (see `HitRecord::uv_footprint`). Starts at the camera with the angle a pixel covers.
+ */
+#[derive(Clone, Copy)]
+pub struct RayCone {
+    pub width: f64, // Footprint width at the ray's origin
+    pub spread: f64, // Growth of the width per unit distance (the cone's angle in radians)
+}
+
+impl RayCone {
+    // Footprint width at a distance along the ray
+    pub fn width_at(&self, distance: f64) -> f64 {
+        self.width + self.spread * distance
     }
+}
 
+impl Default for Ray {
+    fn default() -> Self {
+        Self { origin: Vec3::new(0.0, 0.0, 0.0), direction: Vec3::new(0.0, 0.0, 0.0), wavelengths: None, medium: None, cone: None }
+    }
+}
+
+impl Ray {
     pub fn new(origin: Vec3, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+        Ray { origin, direction, wavelengths: None, medium: None, cone: None }
     }
 
     // Getters
     pub fn origin(&self) -> Vec3 { self.origin }
     pub fn direction(&self) -> Vec3 { self.direction }
+    pub fn wavelengths(&self) -> Option<SampledWavelengths> { self.wavelengths }
+    pub fn medium(&self) -> Option<Medium> { self.medium }
+    pub fn cone(&self) -> Option<RayCone> { self.cone }
+
+    pub fn set_wavelengths(&mut self, wavelengths: Option<SampledWavelengths>) {
+        self.wavelengths = wavelengths;
+    }
+
+    pub fn set_medium(&mut self, medium: Option<Medium>) {
+        self.medium = medium;
+    }
+
+    pub fn set_cone(&mut self, cone: Option<RayCone>) {
+        self.cone = cone;
+    }
 
     // Returns the point at parameter t along the ray
     pub fn at(&self, t: f64) -> Vec3 {
@@ -0,0 +1,136 @@
+/*
+ * Rough dielectric (frosted glass, etched acrylic, rough ice)
+ *
+ * Microfacet transmission model from Walter et al. 2007, "Microfacet Models for Refraction through Rough Surfaces".
+ * Each microfacet is a tiny smooth glass interface: we sample a visible GGX microfacet normal, then either
+ * reflect off it or refract through it, choosing with its exact Fresnel reflectance.
+ *
+ * Since the choice is made with probability F and the normal is sampled from the visible normal distribution,
+ * the ray weight for both reflection and transmission reduces to G2 / G1. Perfectly smooth glass is sampled
+ * like Dielectric (a specular sample), rough glass can also be evaluated for light sampling.
+ *
+ * Roughness is a texture (red channel in [0, 1]), so frost can vary across a surface.
+ * Like Dielectric, the inside can absorb light (Beer-Lambert law), see `with_absorption`.
+ */
+
+use std::sync::Arc;
+
+use super::{color::Color, hittable::HitRecord, material::{absorption_from_transmission, interior_transmittance, scatter_from_sample, BsdfSample, Material}, microfacet::{fresnel_dielectric, rough_dielectric, TrowbridgeReitz}, onb::Onb, ray::Ray, texture::{IntoTexture, Texture}, utils::random_double, vec3::{reflect, refract, Vec3}};
+
+pub struct RoughDielectric {
+    refraction_index: f64,
+    roughness: Arc<dyn Texture>,
+    absorption: Color, // Absorption coefficient per unit distance traveled inside, per channel
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f64, roughness: f64) -> Self {
+        Self::from_texture(refraction_index, roughness)
+    }
+
+    pub fn from_texture(refraction_index: f64, roughness: impl IntoTexture) -> Self {
+        Self { refraction_index, roughness: roughness.into_texture(), absorption: Color::zero() }
+    }
+
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    pub fn with_transmission_color(self, color: Color, distance: f64) -> Self {
+        self.with_absorption(absorption_from_transmission(color, distance))
+    }
+
+    // Relative index of refraction across the interface, as seen from the incoming side
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face { self.refraction_index } else { 1.0 / self.refraction_index }
+    }
+
+    fn distribution(&self, rec: &HitRecord) -> TrowbridgeReitz {
+        let roughness = self.roughness.value_at(rec).x().clamp(0.0, 1.0);
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+        TrowbridgeReitz::new(alpha, alpha)
+    }
+
+    // Distribution and local directions for evaluating the BSDF, None for smooth glass (nothing to evaluate)
+    fn local_directions(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Option<(TrowbridgeReitz, Vec3, Vec3)> {
+        let distribution = self.distribution(rec);
+        if distribution.effectively_smooth() {
+            return None;
+        }
+
+        let frame = Onb::from_normal_tangent(rec.normal, rec.tangent);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        let wi = frame.to_local(direction.unit_vector());
+        Some((distribution, wo, wi))
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        scatter_from_sample(self.sample(r_in, rec), attenuation, scattered)
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let transmittance = interior_transmittance(self.absorption, r_in, rec);
+        let eta = self.eta(rec);
+        let distribution = self.distribution(rec);
+
+        // Local frame with the normal facing the incoming ray
+        let frame = Onb::from_normal_tangent(rec.normal, rec.tangent);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        // Perfectly smooth: the microfacet normal is the surface normal
+        let smooth = distribution.effectively_smooth();
+        let wm = if smooth { frame.to_local(rec.normal) } else { distribution.sample_wm(wo, random_double(), random_double()) };
+
+        let reflectance = fresnel_dielectric(wo.dot(&wm), eta);
+        let wi = if random_double() < reflectance {
+            let wi = reflect(-wo, wm);
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            let wi = refract(-wo, wm, 1.0 / eta);
+            if wi.z() >= 0.0 {
+                return None;
+            }
+            wi
+        };
+
+        let scattered = Ray::new(rec.p, frame.transform(wi));
+        if smooth {
+            return Some(BsdfSample { scattered, weight: transmittance, pdf: 0.0, is_specular: true });
+        }
+        Some(BsdfSample {
+            scattered,
+            weight: transmittance * (distribution.g(wo, wi) / distribution.g1(wo)),
+            pdf: rough_dielectric(&distribution, wo, wi, eta).1,
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        match self.local_directions(r_in, rec, direction) {
+            Some((distribution, wo, wi)) => {
+                interior_transmittance(self.absorption, r_in, rec) * rough_dielectric(&distribution, wo, wi, self.eta(rec)).0
+            }
+            None => Color::zero(),
+        }
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        match self.local_directions(r_in, rec, direction) {
+            Some((distribution, wo, wi)) => rough_dielectric(&distribution, wo, wi, self.eta(rec)).1,
+            None => 0.0,
+        }
+    }
+
+    fn albedo(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        interior_transmittance(self.absorption, r_in, rec)
+    }
+}
@@ -0,0 +1,205 @@
+/*
+ * Spectral rendering
+ *
+ * In spectral mode each camera ray carries a few sampled wavelengths instead of R, G and B. Paths are
+ * traced with per-wavelength values (stored in the 3 lanes of a Color so the integrator math doesn't change),
+ * and the result is converted back to RGB with the CIE 1931 color matching functions.
+ *
+ * Hero wavelength sampling (Wilkie et al. 2014): one "hero" wavelength is picked uniformly in the visible range
+ * and the other lanes are evenly spaced from it (wrapping around), which covers the spectrum well with few lanes.
+ * Wavelength dependent events like dispersion can only follow one wavelength, so they terminate the secondary
+ * lanes and the hero continues alone.
+ *
+ * RGB colors (albedos, sky) are upsampled to smooth spectra with a simple partition-of-unity basis, so a color
+ * component of 1 everywhere stays a flat spectrum of 1 (energy conserving for albedos) and neutral grays stay neutral.
+ */
+
+use std::sync::OnceLock;
+
+use super::{color::Color, ray::Ray};
+
+pub const LAMBDA_MIN: f64 = 380.0; // nm
+pub const LAMBDA_MAX: f64 = 720.0; // nm
+pub const N_WAVELENGTHS: usize = 3;
+
+#[derive(Clone, Copy)]
+pub struct SampledWavelengths {
+    lambda: [f64; N_WAVELENGTHS], // nm
+    pdf: [f64; N_WAVELENGTHS],
+    terminated: bool, // Only the hero wavelength (lane 0) is still being followed
+}
+
+impl SampledWavelengths {
+    // Hero wavelength from a uniform random number u in [0, 1), the others evenly spaced after it
+    pub fn sample_uniform(u: f64) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = LAMBDA_MIN + u * range;
+        let delta = range / N_WAVELENGTHS as f64;
+
+        let mut lambda = [hero; N_WAVELENGTHS];
+        for (i, l) in lambda.iter_mut().enumerate().skip(1) {
+            *l = hero + i as f64 * delta;
+            if *l > LAMBDA_MAX {
+                *l -= range;
+            }
+        }
+
+        Self { lambda, pdf: [1.0 / range; N_WAVELENGTHS], terminated: false }
+    }
+
+    pub fn lambda(&self, i: usize) -> f64 { self.lambda[i] }
+    pub fn hero(&self) -> f64 { self.lambda[0] }
+    pub fn is_terminated(&self) -> bool { self.terminated }
+
+    /*
+     * Stops following the secondary wavelengths. The throughput of a path crossing this event must be
+     * multiplied by `termination_weight` so the hero alone stays an unbiased estimate.
+     */
+    pub fn terminate_secondary(mut self) -> Self {
+        self.terminated = true;
+        self
+    }
+
+    pub fn termination_weight() -> Color {
+        Color::new(N_WAVELENGTHS as f64, 0.0, 0.0)
+    }
+}
+
+/*
+ * Upsamples an RGB color to a smooth spectrum and evaluates it at one wavelength.
+ *
+ * The basis functions are normalized Gaussians around red, green and blue that sum to 1 at every wavelength.
+ * Centers and widths were fit so that RGB -> spectrum -> RGB round trips closely for primaries and typical albedos.
+ */
+pub fn rgb_to_spectrum(rgb: Color, lambda: f64) -> f64 {
+    let g = |center: f64, width: f64| f64::exp(-0.5 * ((lambda - center) / width).powi(2));
+    let r_basis = g(630.0, 15.0);
+    let g_basis = g(520.0, 25.0);
+    let b_basis = g(450.0, 30.0);
+    let sum = r_basis + g_basis + b_basis;
+
+    (rgb.x() * r_basis + rgb.y() * g_basis + rgb.z() * b_basis) / sum
+}
+
+/*
+ * Converts an RGB color into the per-wavelength values of the path a ray belongs to.
+ * RGB rays (no wavelengths) get the color unchanged.
+ */
+pub fn to_path_space(rgb: Color, r: &Ray) -> Color {
+    match r.wavelengths() {
+        Some(wavelengths) => Color::new(
+            rgb_to_spectrum(rgb, wavelengths.lambda(0)),
+            rgb_to_spectrum(rgb, wavelengths.lambda(1)),
+            rgb_to_spectrum(rgb, wavelengths.lambda(2)),
+        ),
+        None => rgb,
+    }
+}
+
+/*
+ * CIE 1931 2-degree color matching functions, using the multi-lobe Gaussian fit from
+ * Wyman, Sloan & Shirley 2013, "Simple Analytic Approximations to the CIE XYZ Color Matching Functions".
+ */
+pub fn cie_xyz(lambda: f64) -> Color {
+    let g = |mu: f64, sigma_low: f64, sigma_high: f64| {
+        let t = (lambda - mu) / if lambda < mu { sigma_low } else { sigma_high };
+        f64::exp(-0.5 * t * t)
+    };
+
+    let x = 1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2);
+    let y = 0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1);
+    let z = 1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8);
+    Color::new(x, y, z)
+}
+
+// CIE XYZ to linear sRGB (D65)
+pub fn xyz_to_rgb(xyz: Color) -> Color {
+    let (x, y, z) = (xyz.x(), xyz.y(), xyz.z());
+    Color::new(
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    )
+}
+
+/*
+ * Monte Carlo estimate of the RGB color of a spectrum sampled at the given wavelengths.
+ *
+ * XYZ = (1 / n) * sum of L(lambda_i) * cmf(lambda_i) / pdf(lambda_i), normalized so a flat spectrum of 1 has Y = 1.
+ * The result is white balanced so a flat spectrum of 1 comes out as RGB (1, 1, 1).
+ */
+pub fn spectrum_to_rgb(values: Color, wavelengths: &SampledWavelengths) -> Color {
+    let values = [values.x(), values.y(), values.z()];
+    let mut xyz = Color::zero();
+    for (i, value) in values.iter().enumerate() {
+        xyz += (value / wavelengths.pdf[i]) * cie_xyz(wavelengths.lambda(i));
+    }
+    xyz /= N_WAVELENGTHS as f64 * cie_y_integral();
+
+    let rgb = xyz_to_rgb(xyz);
+    let white = white_rgb();
+    Color::new(rgb.x() / white.x(), rgb.y() / white.y(), rgb.z() / white.z())
+}
+
+// Integral of the y color matching function over the sampled range
+fn cie_y_integral() -> f64 {
+    static INTEGRAL: OnceLock<f64> = OnceLock::new();
+    *INTEGRAL.get_or_init(|| integrate(cie_xyz).y())
+}
+
+// RGB of a flat spectrum of 1, used to white balance
+fn white_rgb() -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    *WHITE.get_or_init(|| xyz_to_rgb(integrate(cie_xyz) / cie_y_integral()))
+}
+
+// Numerically integrates over [LAMBDA_MIN, LAMBDA_MAX] with 1nm steps (midpoint rule)
+fn integrate<F: Fn(f64) -> Color>(f: F) -> Color {
+    let mut sum = Color::zero();
+    let mut lambda = LAMBDA_MIN + 0.5;
+    while lambda < LAMBDA_MAX {
+        sum += f(lambda);
+        lambda += 1.0;
+    }
+    sum
+}
+
+/*
+ * Wavelength dependent index of refraction, for dispersion (prisms, diamonds).
+ * Formulas use wavelengths in micrometers.
+ */
+#[derive(Clone, Copy)]
+pub enum Dispersion {
+    // n = a + b / lambda^2
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum of b_i lambda^2 / (lambda^2 - c_i)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    // Schott N-BK7 crown glass
+    pub fn bk7() -> Self {
+        Self::Sellmeier { b: [1.03961212, 0.231792344, 1.01046945], c: [0.00600069867, 0.0200179144, 103.560653] }
+    }
+
+    // Dense flint glass (Schott N-SF11), strongly dispersive
+    pub fn dense_flint() -> Self {
+        Self::Sellmeier { b: [1.73759695, 0.313747346, 1.89878101], c: [0.013188707, 0.0623068142, 155.23629] }
+    }
+
+    pub fn diamond() -> Self {
+        Self::Sellmeier { b: [0.3306, 4.3356, 0.0], c: [0.030625, 0.011236, 0.0] }
+    }
+
+    pub fn ior(&self, lambda_nm: f64) -> f64 {
+        let l = lambda_nm / 1000.0;
+        let l2 = l * l;
+        match self {
+            Self::Cauchy { a, b } => a + b / l2,
+            Self::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>();
+                n2.sqrt()
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use super::{hittable::{HitRecord, Hittable}, interval::Interval, material::Material, ray::Ray, vec3::Point3};
+use super::{color::luminance, hittable::{HitRecord, Hittable}, interval::Interval, light::LightBounds, material::Material, onb::Onb, ray::Ray, utils::{random_double, INFINITY, PI}, vec3::{Point3, Vec3}};
 
 
 pub struct Sphere {
@@ -13,6 +13,21 @@ impl Sphere {
     pub fn new(center: Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
         Self { center, radius: f64::max(radius, 0.0), mat }
     }
+
+    /*
+     * Spherical (u, v) mapping of a point p on the unit sphere centered at the origin
+     *
+     * theta: angle down from the -y pole (0 at y = -1, pi at y = +1)
+     * phi: angle around the y-axis (0 at -x, then -z, +x, +z, back to -x)
+     *
+     * u = phi / (2 * pi) and v = theta / pi, both in [0, 1]
+     */
+    fn get_sphere_uv(p: Point3) -> (f64, f64) {
+        let theta = f64::acos(-p.y());
+        let phi = f64::atan2(-p.z(), p.x()) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
 }
 
 // Implements the Hittable trait for Sphere objects 
@@ -41,21 +56,85 @@ impl Hittable for Sphere {
         }
         
         let sqrt_d = discriminant.sqrt();
-        // Find the nearest root that lies in the acceptable range
-        let mut root = (h - sqrt_d) / a;
-        if !ray_t.surrounds(root) {
-            root = (h + sqrt_d) / a;
+        // Try the nearest root that lies in the acceptable range first. The far root is the next candidate,
+        // either because the near one is out of range or because the material cut the surface out there (alpha).
+        for root in [(h - sqrt_d) / a, (h + sqrt_d) / a] {
             if !ray_t.surrounds(root) {
-                return false;
+                continue;
+            }
+
+            rec.t = root;
+            rec.p = r.at(rec.t);
+            let outward_normal = (rec.p - self.center) / self.radius;
+            rec.set_face_normal(r, outward_normal);
+            (rec.u, rec.v) = Self::get_sphere_uv(outward_normal);
+            // dp/du points around the y-axis in the direction of increasing phi
+            rec.set_tangent_frame(outward_normal, Vec3::new(outward_normal.z(), 0.0, -outward_normal.x()));
+            // |dp/du| = 2 pi r sin(theta), |dp/dv| = pi r
+            let sin_theta = f64::sqrt((1.0 - outward_normal.y() * outward_normal.y()).max(0.0));
+            rec.set_uv_footprint(r, 2.0 * PI * self.radius * sin_theta, PI * self.radius);
+
+            if self.mat.is_opaque_at(rec) {
+                rec.mat = self.mat.clone();
+                return true;
             }
         }
 
-        rec.t = root;
-        rec.p = r.at(rec.t);
-        let outward_normal = (rec.p - self.center) / self.radius;
-        rec.set_face_normal(r, outward_normal);
-        rec.mat = self.mat.clone();
+        false
+    }
+
+    /*
+     * Seen from outside, a sphere covers a cone of directions with half angle theta_max, where
+     * sin(theta_max) = radius / distance. Sampling uniformly inside that cone only picks directions that
+     * hit the sphere, with pdf 1 / solid angle = 1 / (2 * pi * (1 - cos(theta_max))).
+     *
+     * Note: Points inside the sphere can't sample it (the cone would cover everything).
+     */
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        if !self.hit(&Ray::new(origin, direction), Interval::new(0.001, INFINITY), &mut rec) {
+            return 0.0;
+        }
 
-        return true;
+        let distance_squared = (self.center - origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return 0.0;
+        }
+        let cos_theta_max = f64::sqrt(1.0 - self.radius * self.radius / distance_squared);
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return direction;
+        }
+
+        let frame = Onb::new(direction);
+        frame.transform(random_to_sphere(self.radius, distance_squared))
     }
+
+    // A diffuse emitter sends out pi * radiance per unit area
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let area = 4.0 * PI * self.radius * self.radius;
+        let power = PI * area * luminance(self.mat.average_emitted());
+        Some(LightBounds { center: self.center, radius: self.radius, power })
+    }
+}
+
+// Uniform random direction (around +z) inside the cone subtended by a sphere at the given squared distance
+fn random_to_sphere(radius: f64, distance_squared: f64) -> Vec3 {
+    let r1 = random_double();
+    let r2 = random_double();
+    let cos_theta_max = f64::sqrt(1.0 - radius * radius / distance_squared);
+    let z = 1.0 + r2 * (cos_theta_max - 1.0);
+
+    let phi = 2.0 * PI * r1;
+    let x = f64::cos(phi) * f64::sqrt(1.0 - z * z);
+    let y = f64::sin(phi) * f64::sqrt(1.0 - z * z);
+
+    Vec3::new(x, y, z)
 }
\ No newline at end of file
@@ -0,0 +1,63 @@
+/*
+ * Subsurface scattering (skin, wax, marble, milk, jade)
+ *
+ * A smooth dielectric boundary filled with a scattering medium. Light that refracts in does a random walk:
+ * it travels a random distance (based on the mean free path), scatters off a particle in a random direction,
+ * and repeats until it's absorbed or reaches the boundary again where it can refract back out, possibly far
+ * from where it entered. That light bleeding through the surface is what makes these materials look soft.
+ *
+ * The material only handles the boundary (Fresnel reflection/refraction) and tags rays refracted inwards
+ * with the medium. The walk itself happens in the camera integrator, which samples distances for rays
+ * traveling inside a medium.
+ *
+ * Note: Each step of the walk counts as a bounce, so raise `Camera::max_depth` for dense media.
+ */
+
+use super::{color::Color, hittable::HitRecord, material::Material, medium::Medium, microfacet::fresnel_dielectric, ray::Ray, utils::random_double, vec3::{reflect, refract}};
+
+pub struct Subsurface {
+    refraction_index: f64,
+    medium: Medium,
+}
+
+impl Subsurface {
+    /*
+     * albedo: color left after each scattering event (close to 1 for bright, milky materials)
+     * mean_free_path: average distance light travels between scattering events per channel, in scene units
+     *                 (longer for red in skin, which is why ears glow red)
+     */
+    pub fn new(refraction_index: f64, albedo: Color, mean_free_path: Color) -> Self {
+        Self::anisotropic(refraction_index, albedo, mean_free_path, 0.0)
+    }
+
+    // g > 0 makes particles scatter mostly forward (e.g. skin is around 0.8)
+    pub fn anisotropic(refraction_index: f64, albedo: Color, mean_free_path: Color, g: f64) -> Self {
+        Self { refraction_index, medium: Medium::from_albedo(albedo, mean_free_path, g) }
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        *attenuation = Color::new(1.0, 1.0, 1.0);
+
+        let eta = if rec.front_face { self.refraction_index } else { 1.0 / self.refraction_index };
+        let unit_direction = r_in.direction().unit_vector();
+        let cos_theta = f64::min(-unit_direction.dot(&rec.normal), 1.0);
+
+        // Reflect (and stay on the side we came from) or refract through the boundary
+        let reflected = random_double() < fresnel_dielectric(cos_theta, eta);
+        let direction = if reflected { reflect(unit_direction, rec.normal) } else { refract(unit_direction, rec.normal, 1.0 / eta) };
+        let inside = rec.front_face != reflected;
+
+        *scattered = Ray::new(rec.p, direction);
+        if inside {
+            scattered.set_medium(Some(self.medium));
+        }
+        true
+    }
+
+    // Color of the scattering inside, the boundary itself is clear
+    fn albedo(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        self.medium.albedo()
+    }
+}
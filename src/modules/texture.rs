@@ -0,0 +1,250 @@
+/*
+ * Texture
+ *
+ * A texture maps a surface coordinate to a color. Surfaces report (u, v) texture coordinates in [0, 1]
+ * along with the hit point p, so a texture can either be "painted" onto the surface using (u, v)
+ * or be "solid" (defined everywhere in space) using p.
+ */
+
+use std::sync::Arc;
+
+use super::{color::Color, hittable::HitRecord, perlin::Perlin, vec3::Point3, worley::{hash_to_unit, Worley}};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+
+    /*
+     * Value at a hit point. The hit also knows how much of the texture the pixel covers there (uv_footprint),
+     * which filtered textures (mipmapped images) use to pick their level of detail.
+     */
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.value(rec.u, rec.v, &rec.p)
+    }
+}
+
+/*
+ * Solid color texture: the same color everywhere.
+ * Lets materials always take a texture, even when all we want is a constant albedo.
+ */
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+
+    pub fn from_rgb(red: f64, green: f64, blue: f64) -> Self {
+        Self::new(Color::new(red, green, blue))
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.albedo
+    }
+}
+
+/*
+ * Anything that can stand in for a texture parameter: a scalar (gray), a constant color or an actual texture.
+ * Lets materials with many parameters take `0.5`, `Color::new(..)` or `Arc::new(NoiseTexture::new(..))` alike.
+ */
+pub trait IntoTexture {
+    fn into_texture(self) -> Arc<dyn Texture>;
+}
+
+impl IntoTexture for f64 {
+    fn into_texture(self) -> Arc<dyn Texture> {
+        Arc::new(SolidColor::from_rgb(self, self, self))
+    }
+}
+
+impl IntoTexture for Color {
+    fn into_texture(self) -> Arc<dyn Texture> {
+        Arc::new(SolidColor::new(self))
+    }
+}
+
+impl IntoTexture for Arc<dyn Texture> {
+    fn into_texture(self) -> Arc<dyn Texture> {
+        self
+    }
+}
+
+impl<T: Texture + 'static> IntoTexture for Arc<T> {
+    fn into_texture(self) -> Arc<dyn Texture> {
+        self
+    }
+}
+
+/*
+ * Checker texture: a 3D "solid" checkerboard alternating between two textures.
+ *
+ * Space is divided into cubes of side `scale`. The sum of a point's integer cube coordinates
+ * decides whether it uses the even or the odd texture.
+ */
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: impl IntoTexture, odd: impl IntoTexture) -> Self {
+        Self { inv_scale: 1.0 / scale, even: even.into_texture(), odd: odd.into_texture() }
+    }
+
+    pub fn from_colors(scale: f64, c1: Color, c2: Color) -> Self {
+        Self::new(scale, c1, c2)
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let x = (self.inv_scale * p.x()).floor() as i64;
+        let y = (self.inv_scale * p.y()).floor() as i64;
+        let z = (self.inv_scale * p.z()).floor() as i64;
+
+        if (x + y + z).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/*
+ * Perlin noise texture: grayscale noise remapped from [-1, 1] to [0, 1].
+ * Higher scale means higher frequency (smaller blobs).
+ */
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64) -> Self {
+        Self { noise: Perlin::new(), scale }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        Color::new(1.0, 1.0, 1.0) * 0.5 * (1.0 + self.noise.noise(&(self.scale * *p)))
+    }
+}
+
+/*
+ * Turbulence texture: grayscale fBm (several octaves of Perlin noise summed together).
+ */
+pub struct TurbulenceTexture {
+    noise: Perlin,
+    scale: f64,
+    depth: u32, // Number of octaves
+}
+
+impl TurbulenceTexture {
+    pub fn new(scale: f64, depth: u32) -> Self {
+        Self { noise: Perlin::new(), scale, depth }
+    }
+}
+
+impl Texture for TurbulenceTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        Color::new(1.0, 1.0, 1.0) * self.noise.turb(&(self.scale * *p), self.depth)
+    }
+}
+
+/*
+ * Marble texture: regular stripes along z whose phase is pushed around by turbulence,
+ * which gives the swirly veins of marble.
+ */
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl MarbleTexture {
+    pub fn new(scale: f64) -> Self {
+        Self { noise: Perlin::new(), scale }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let stripes = 0.5 * (1.0 + f64::sin(self.scale * p.z() + 10.0 * self.noise.turb(p, 7)));
+        Color::new(1.0, 1.0, 1.0) * stripes
+    }
+}
+
+/*
+ * Wood texture: concentric growth rings around the y-axis, made irregular with a bit of turbulence.
+ * The fractional part of the (perturbed) ring index blends between the light and dark wood colors.
+ */
+pub struct WoodTexture {
+    noise: Perlin,
+    scale: f64, // Number of rings per unit distance from the axis
+    light: Color,
+    dark: Color,
+}
+
+impl WoodTexture {
+    pub fn new(scale: f64) -> Self {
+        Self::from_colors(scale, Color::new(0.79, 0.6, 0.4), Color::new(0.45, 0.28, 0.14))
+    }
+
+    pub fn from_colors(scale: f64, light: Color, dark: Color) -> Self {
+        Self { noise: Perlin::new(), scale, light, dark }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let radius = f64::sqrt(p.x() * p.x() + p.z() * p.z());
+        let rings = self.scale * radius + 2.0 * self.noise.turb(p, 4);
+        let t = rings - rings.floor();
+
+        // Sharpen the rings so the dark band is thinner than the light one
+        let t = t * t;
+        (1.0 - t) * self.light + t * self.dark
+    }
+}
+
+// What a Voronoi texture outputs for each point
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum VoronoiMode {
+    Distance, // Grayscale distance to the closest feature point (F1)
+    Edges, // Grayscale distance to the closest cell border (F2 - F1)
+    Cells, // Flat random color per cell
+}
+
+/*
+ * Voronoi texture based on Worley cellular noise, good for cells, scales, cracked mud or stone tiles.
+ */
+pub struct VoronoiTexture {
+    noise: Worley,
+    scale: f64,
+    mode: VoronoiMode,
+}
+
+impl VoronoiTexture {
+    pub fn new(scale: f64, mode: VoronoiMode) -> Self {
+        Self { noise: Worley::new(), scale, mode }
+    }
+}
+
+impl Texture for VoronoiTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let sample = self.noise.sample(&(self.scale * *p));
+
+        match self.mode {
+            VoronoiMode::Distance => Color::new(1.0, 1.0, 1.0) * f64::min(sample.f1, 1.0),
+            VoronoiMode::Edges => Color::new(1.0, 1.0, 1.0) * f64::min(sample.f2 - sample.f1, 1.0),
+            VoronoiMode::Cells => {
+                let id = sample.cell_id;
+                Color::new(hash_to_unit(id >> 3), hash_to_unit(id >> 24), hash_to_unit(id >> 43))
+            }
+        }
+    }
+}
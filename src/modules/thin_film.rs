@@ -0,0 +1,258 @@
+/*
+ * Thin-film interference (soap bubbles, oil slicks, beetle shells)
+ *
+ * A dielectric film only a few hundred nanometers thick sits on top of a base. Light reflecting off the top
+ * of the film interferes with light reflecting off the bottom, and since the path difference depends on the
+ * wavelength, some colors are reinforced and others cancelled, giving the rainbow-like sheen.
+ *
+ * The film reflectance is the Airy summation of all the internal bounces, evaluated for s and p polarization
+ * and averaged. It's computed at a representative wavelength for each RGB channel. Light that isn't
+ * reflected by the film goes through to the base material.
+ *
+ * In spectral mode it's computed at the ray's hero wavelength instead. Like dispersion, that's a wavelength
+ * dependent event the secondary wavelengths can't follow, so they're terminated at the first film hit, and
+ * until then the film isn't light sampled (its `eval` would only be right for the hero).
+ *
+ * Interfaces: outside (outer_ior, 1 for air) -> film (film_ior) -> base (base_ior).
+ *
+ * The film reflectance already includes the reflection at the film -> base interface, so a base material that
+ * has its own reflecting surface (Dielectric, RoughDielectric, Coated, ...) would count it twice. `new` is for
+ * bases without one, like diffuse, metal or conductor bases (oil on asphalt, tempered steel). For a film on a
+ * clear dielectric (soap bubbles, coated lenses) use `on_dielectric`: the light the film doesn't reflect
+ * refracts straight into the base, from either side of the surface.
+ */
+
+use std::sync::Arc;
+
+use super::{color::Color, hittable::HitRecord, material::{scatter_from_sample, BsdfSample, Material}, ray::Ray, spectral::SampledWavelengths, texture::{IntoTexture, Texture}, utils::{random_double, PI}, vec3::{reflect, refract, Vec3}};
+
+// Wavelengths (in nm) used for the R, G and B channels
+pub const RGB_WAVELENGTHS: [f64; 3] = [630.0, 532.0, 465.0];
+
+pub struct ThinFilm {
+    base: Option<Arc<dyn Material>>, // None for a clear dielectric base of base_ior, see `on_dielectric`
+    outer_ior: f64, // Index of refraction of what's around the film, e.g. glass for a bubble inside glass
+    film_ior: f64,
+    base_ior: f64, // Index of refraction under the film, only used for the interference
+    thickness: Arc<dyn Texture>, // Maps to [min_thickness, max_thickness] via its red channel
+    min_thickness: f64, // nm
+    max_thickness: f64, // nm
+}
+
+impl ThinFilm {
+    pub fn new(base: Arc<dyn Material>, film_ior: f64, thickness: f64) -> Self {
+        Self::from_base(Some(base), film_ior, thickness)
+    }
+
+    // Film on a clear dielectric of IOR base_ior, e.g. 1 for the air inside a soap bubble
+    pub fn on_dielectric(base_ior: f64, film_ior: f64, thickness: f64) -> Self {
+        Self::from_base(None, film_ior, thickness).with_base_ior(base_ior)
+    }
+
+    fn from_base(base: Option<Arc<dyn Material>>, film_ior: f64, thickness: f64) -> Self {
+        Self {
+            base,
+            outer_ior: 1.0,
+            film_ior,
+            base_ior: 1.0,
+            thickness: 1.0.into_texture(),
+            min_thickness: thickness,
+            max_thickness: thickness,
+        }
+    }
+
+    pub fn with_outer_ior(mut self, outer_ior: f64) -> Self {
+        self.outer_ior = outer_ior;
+        self
+    }
+
+    pub fn with_base_ior(mut self, base_ior: f64) -> Self {
+        self.base_ior = base_ior;
+        self
+    }
+
+    // Thickness varying across the surface, e.g. a noise texture for swirling soap bubble colors
+    pub fn with_thickness_variation(mut self, variation: impl IntoTexture, min_thickness: f64, max_thickness: f64) -> Self {
+        self.thickness = variation.into_texture();
+        self.min_thickness = min_thickness;
+        self.max_thickness = max_thickness;
+        self
+    }
+
+    fn thickness_at(&self, rec: &HitRecord) -> f64 {
+        let t = self.thickness.value_at(rec).x().clamp(0.0, 1.0);
+        self.min_thickness + t * (self.max_thickness - self.min_thickness)
+    }
+}
+
+impl ThinFilm {
+    // IORs on the side the light comes from and on the other side of the film
+    fn outside_inside_iors(&self, rec: &HitRecord) -> (f64, f64) {
+        if rec.front_face { (self.outer_ior, self.base_ior) } else { (self.base_ior, self.outer_ior) }
+    }
+
+    // Film reflectance per channel for light arriving along r_in, the same for all channels at a spectral ray's hero wavelength
+    fn reflectance(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        let cos_theta = f64::min(-r_in.direction().unit_vector().dot(&rec.normal), 1.0);
+        let thickness = self.thickness_at(rec);
+        let (outside_ior, inside_ior) = self.outside_inside_iors(rec);
+        let reflectance = |lambda| thin_film_reflectance(cos_theta, outside_ior, self.film_ior, inside_ior, thickness, lambda);
+        match r_in.wavelengths() {
+            Some(wavelengths) => {
+                let hero = reflectance(wavelengths.hero());
+                Color::new(hero, hero, hero)
+            }
+            None => {
+                let [r, g, b] = RGB_WAVELENGTHS.map(reflectance);
+                Color::new(r, g, b)
+            }
+        }
+    }
+}
+
+// Spectral ray whose secondary wavelengths are still followed (and must be terminated by the film)
+fn unterminated_wavelengths(r_in: &Ray) -> Option<SampledWavelengths> {
+    r_in.wavelengths().filter(|wavelengths| !wavelengths.is_terminated())
+}
+
+// Probability of sampling the film reflection: the average reflectance, kept away from 0 and 1
+fn reflect_probability(reflectance: Color) -> f64 {
+    reflectance.average().clamp(1e-4, 1.0 - 1e-4)
+}
+
+/*
+ * The film reflection is a mirror (a specular sample). Light going through the film is sampled from the base,
+ * then scaled by the fraction the film let through, so rough or diffuse bases keep their `eval`/`pdf`.
+ * Without a base material it refracts from the outside IOR straight into the base IOR (the film's own bending
+ * cancels out), which is a specular sample too.
+ *
+ * Back faces are only hit from inside the base: a base material handles them on its own, a clear base goes
+ * through the film again in the other direction.
+ */
+impl Material for ThinFilm {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        scatter_from_sample(self.sample(r_in, rec), attenuation, scattered)
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        if let (false, Some(base)) = (rec.front_face, &self.base) {
+            return base.sample(r_in, rec);
+        }
+
+        // Pick between the film reflection and the base by the average reflectance, then reweight per channel
+        let reflectance = self.reflectance(r_in, rec);
+        let p_reflect = reflect_probability(reflectance);
+        let terminated = unterminated_wavelengths(r_in).map(|wavelengths| wavelengths.terminate_secondary());
+        let unit_direction = r_in.direction().unit_vector();
+        if random_double() < p_reflect {
+            let mut scattered = Ray::new(rec.p, reflect(unit_direction, rec.normal));
+            if terminated.is_some() {
+                scattered.set_wavelengths(terminated);
+            }
+            return Some(BsdfSample { scattered, weight: reflectance / p_reflect, pdf: 0.0, is_specular: true });
+        }
+
+        let transmittance = Color::new(1.0, 1.0, 1.0) - reflectance;
+        let Some(base) = &self.base else {
+            // Total internal reflection (the reflectance is 1) lets nothing through
+            if transmittance.near_zero() {
+                return None;
+            }
+            let (outside_ior, inside_ior) = self.outside_inside_iors(rec);
+            let mut scattered = Ray::new(rec.p, refract(unit_direction, rec.normal, outside_ior / inside_ior));
+            if terminated.is_some() {
+                scattered.set_wavelengths(terminated);
+            }
+            return Some(BsdfSample { scattered, weight: transmittance / (1.0 - p_reflect), pdf: 0.0, is_specular: true });
+        };
+
+        let mut sample = base.sample(r_in, rec)?;
+        sample.weight = sample.weight * transmittance / (1.0 - p_reflect);
+        if terminated.is_some() {
+            sample.scattered.set_wavelengths(terminated);
+            sample.pdf = 0.0;
+            sample.is_specular = true;
+        } else if !sample.is_specular {
+            sample.pdf *= 1.0 - p_reflect;
+        }
+        Some(sample)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        let Some(base) = &self.base else {
+            return Color::zero();
+        };
+        if !rec.front_face {
+            return base.eval(r_in, rec, direction);
+        }
+        if unterminated_wavelengths(r_in).is_some() {
+            return Color::zero();
+        }
+        (Color::new(1.0, 1.0, 1.0) - self.reflectance(r_in, rec)) * base.eval(r_in, rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let Some(base) = &self.base else {
+            return 0.0;
+        };
+        if !rec.front_face {
+            return base.pdf(r_in, rec, direction);
+        }
+        if unterminated_wavelengths(r_in).is_some() {
+            return 0.0;
+        }
+        (1.0 - reflect_probability(self.reflectance(r_in, rec))) * base.pdf(r_in, rec, direction)
+    }
+
+    fn is_opaque_at(&self, rec: &HitRecord) -> bool {
+        self.base.as_ref().is_none_or(|base| base.is_opaque_at(rec))
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.base.as_ref().map_or(Color::zero(), |base| base.emitted(rec))
+    }
+
+    fn average_emitted(&self) -> Color {
+        self.base.as_ref().map_or(Color::zero(), |base| base.average_emitted())
+    }
+}
+
+/*
+ * Reflectance of a thin film (Airy summation), for light coming from a medium of IOR outer_ior.
+ *
+ * With r12 and r23 the Fresnel amplitude coefficients of the top and bottom interfaces and
+ * delta the phase difference accumulated by one round trip through the film:
+ *
+ *     R = (r12^2 + r23^2 + 2 r12 r23 cos(delta)) / (1 + r12^2 r23^2 + 2 r12 r23 cos(delta))
+ *
+ * thickness and wavelength are both in nanometers.
+ */
+pub fn thin_film_reflectance(cos_theta_i: f64, outer_ior: f64, film_ior: f64, base_ior: f64, thickness: f64, wavelength: f64) -> f64 {
+    let cos1 = cos_theta_i.clamp(0.0, 1.0);
+    let sin1 = f64::sqrt(1.0 - cos1 * cos1);
+
+    // Snell's law into the film and into the base
+    let sin2 = outer_ior * sin1 / film_ior;
+    let sin3 = outer_ior * sin1 / base_ior;
+    if sin2 >= 1.0 || sin3 >= 1.0 {
+        return 1.0;
+    }
+    let cos2 = f64::sqrt(1.0 - sin2 * sin2);
+    let cos3 = f64::sqrt(1.0 - sin3 * sin3);
+
+    // Phase difference of one round trip through the film
+    let delta = 2.0 * PI / wavelength * 2.0 * film_ior * thickness * cos2;
+
+    let airy = |r12: f64, r23: f64| {
+        let cross = 2.0 * r12 * r23 * delta.cos();
+        (r12 * r12 + r23 * r23 + cross) / (1.0 + r12 * r12 * r23 * r23 + cross)
+    };
+
+    // s (perpendicular) and p (parallel) polarized amplitude coefficients
+    let r12_s = (outer_ior * cos1 - film_ior * cos2) / (outer_ior * cos1 + film_ior * cos2);
+    let r23_s = (film_ior * cos2 - base_ior * cos3) / (film_ior * cos2 + base_ior * cos3);
+    let r12_p = (film_ior * cos1 - outer_ior * cos2) / (film_ior * cos1 + outer_ior * cos2);
+    let r23_p = (base_ior * cos2 - film_ior * cos3) / (base_ior * cos2 + film_ior * cos3);
+
+    (0.5 * (airy(r12_s, r23_s) + airy(r12_p, r23_p))).clamp(0.0, 1.0)
+}
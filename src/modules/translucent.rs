@@ -0,0 +1,86 @@
+/*
+ * Translucent (diffuse transmission)
+ *
+ * Thin, rough sheets like leaves, paper and lampshades: light is scattered diffusely on both sides,
+ * so the back of a leaf glows when the sun is behind it. There's no refraction and no inside, the
+ * transmitted ray just leaves from the other side of the surface.
+ *
+ * Each scatter picks reflection or transmission with a probability proportional to their brightness,
+ * then samples a cosine distributed direction on that side.
+ *
+ * Note: Works with both sides of a single quad. Keep reflectance + transmittance <= 1 to conserve energy.
+ */
+
+use std::sync::Arc;
+
+use super::{color::Color, hittable::HitRecord, material::{scatter_from_sample, BsdfSample, Material}, onb::Onb, ray::Ray, texture::{IntoTexture, Texture}, utils::{random_double, PI}, vec3::{random_cosine_direction, Vec3}};
+
+pub struct Translucent {
+    reflectance: Arc<dyn Texture>, // Diffuse color on the side the light comes from
+    transmittance: Arc<dyn Texture>, // Diffuse color of the light coming through
+}
+
+impl Translucent {
+    pub fn new(reflectance: impl IntoTexture, transmittance: impl IntoTexture) -> Self {
+        Self { reflectance: reflectance.into_texture(), transmittance: transmittance.into_texture() }
+    }
+
+    // Colors of both lobes and the probability of sampling reflection
+    fn lobes(&self, rec: &HitRecord) -> (Color, Color, f64) {
+        let reflectance = self.reflectance.value_at(rec);
+        let transmittance = self.transmittance.value_at(rec);
+
+        let r = reflectance.average();
+        let t = transmittance.average();
+        let p_reflect = if r + t > 0.0 { r / (r + t) } else { 0.0 };
+        (reflectance, transmittance, p_reflect)
+    }
+}
+
+impl Material for Translucent {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        scatter_from_sample(self.sample(r_in, rec), attenuation, scattered)
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let (reflectance, transmittance, p_reflect) = self.lobes(rec);
+        if reflectance.average() + transmittance.average() <= 0.0 {
+            return None;
+        }
+
+        // The normal faces the incoming ray, so transmitted light leaves around the opposite normal
+        let (side, color, probability) = if random_double() < p_reflect {
+            (rec.normal, reflectance, p_reflect)
+        } else {
+            (-rec.normal, transmittance, 1.0 - p_reflect)
+        };
+        let direction = Onb::new(side).transform(random_cosine_direction());
+
+        Some(BsdfSample {
+            scattered: Ray::new(rec.p, direction),
+            weight: color / probability,
+            pdf: self.pdf(r_in, rec, direction),
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        let (reflectance, transmittance, _) = self.lobes(rec);
+        let cos_theta = rec.normal.dot(&direction.unit_vector());
+        let color = if cos_theta > 0.0 { reflectance } else { transmittance };
+        color * (cos_theta.abs() / PI)
+    }
+
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let (_, _, p_reflect) = self.lobes(rec);
+        let cos_theta = rec.normal.dot(&direction.unit_vector());
+        let probability = if cos_theta > 0.0 { p_reflect } else { 1.0 - p_reflect };
+        probability * cos_theta.abs() / PI
+    }
+
+    // Light is kept whether it's reflected or let through
+    fn albedo(&self, _r_in: &Ray, rec: &HitRecord) -> Color {
+        let (reflectance, transmittance, _) = self.lobes(rec);
+        reflectance + transmittance
+    }
+}
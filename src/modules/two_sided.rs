@@ -0,0 +1,85 @@
+/*
+ * Two-sided surfaces
+ *
+ * Hit records already know which side of a surface was hit (front_face), but most materials treat both
+ * sides the same or, like Dielectric, read the back face as "leaving the object". For thin surfaces with no
+ * inside (signs, paper, leaves modeled as single quads) that's wrong: both sides are outsides.
+ *
+ * TwoSided presents every hit to the wrapped material as a front face hit, and can hold a different material
+ * for each side (e.g. a printed sign on the front and bare metal on the back). That includes emission:
+ * TwoSided(DiffuseLight) is a panel light shining from both faces.
+ */
+
+use std::sync::Arc;
+
+use super::{color::Color, hittable::HitRecord, material::{BsdfSample, Material}, ray::Ray, vec3::Vec3};
+
+pub struct TwoSided {
+    front: Arc<dyn Material>, // Used when the front face (the side the geometric normal points to) is hit
+    back: Arc<dyn Material>, // Used when the back face is hit
+}
+
+impl TwoSided {
+    // Same material on both sides
+    pub fn new(mat: Arc<dyn Material>) -> Self {
+        Self::per_side(mat.clone(), mat)
+    }
+
+    pub fn per_side(front: Arc<dyn Material>, back: Arc<dyn Material>) -> Self {
+        Self { front, back }
+    }
+
+    fn side(&self, rec: &HitRecord) -> &Arc<dyn Material> {
+        if rec.front_face { &self.front } else { &self.back }
+    }
+}
+
+/*
+ * The normal in the hit record already faces the incoming ray, so only front_face needs to change.
+ * The bitangent is flipped along with it to keep the tangent frame right-handed around the flipped normal,
+ * otherwise normal maps on the back side would come out mirrored.
+ */
+fn as_front_face(rec: &HitRecord) -> HitRecord {
+    let mut front = rec.clone();
+    if !rec.front_face {
+        front.front_face = true;
+        front.bitangent = -rec.bitangent;
+    }
+    front
+}
+
+impl Material for TwoSided {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+        self.side(rec).scatter(r_in, &as_front_face(rec), attenuation, scattered)
+    }
+
+    fn is_opaque_at(&self, rec: &HitRecord) -> bool {
+        self.side(rec).is_opaque_at(&as_front_face(rec))
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        self.side(rec).sample(r_in, &as_front_face(rec))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.side(rec).eval(r_in, &as_front_face(rec), direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        self.side(rec).pdf(r_in, &as_front_face(rec), direction)
+    }
+
+    // Seen as front faces, one-sided emitters (DiffuseLight) light both sides
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.side(rec).emitted(&as_front_face(rec))
+    }
+
+    // Both sides emit, so the surface gives off the light of both materials
+    fn average_emitted(&self) -> Color {
+        self.front.average_emitted() + self.back.average_emitted()
+    }
+
+    fn albedo(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        self.side(rec).albedo(r_in, &as_front_face(rec))
+    }
+}
@@ -2,7 +2,7 @@ use rand::Rng;
 
 // Constants
 pub const INFINITY: f64 = f64::INFINITY;
-pub const PI: f64 = 3.1415926535897932385;
+pub const PI: f64 = std::f64::consts::PI;
 
 // Utility functions
 pub fn degrees_to_radians(degrees: f64) -> f64 {
@@ -16,4 +16,14 @@ pub fn random_double() -> f64 {
 
 pub fn random_double_range(min: f64, max: f64) -> f64 {
     min + (max - min) * random_double()
-}
\ No newline at end of file
+}
+
+/*
+ * Power heuristic (beta = 2) for multiple importance sampling: the weight of a sample taken with density
+ * pdf_f when the same direction could also have been sampled with density pdf_g.
+ */
+pub fn power_heuristic(pdf_f: f64, pdf_g: f64) -> f64 {
+    let f = pdf_f * pdf_f;
+    let g = pdf_g * pdf_g;
+    if f + g <= 0.0 { 0.0 } else { f / (f + g) }
+}
@@ -4,7 +4,7 @@
 
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub};
 
-use super::utils::{random_double, random_double_range};
+use super::utils::{random_double, random_double_range, PI};
 
 // Note: Define the Vec3 struct (what it is)
 // Note: Structs create instances of data vs. traits define shared behavior (polymorphism)
@@ -47,7 +47,7 @@ impl Vec3 {
 
     // Unit vector
     pub fn unit_vector(&self) -> Vec3 {
-        self.clone() / self.length()
+        *self / self.length()
     }
 
     // Dot product
@@ -57,6 +57,11 @@ impl Vec3 {
         self.e[2] * other.e[2]
     }
 
+    // Mean of the components, e.g. the average of a color's channels
+    pub fn average(&self) -> f64 {
+        (self.e[0] + self.e[1] + self.e[2]) / 3.0
+    }
+
     // Near zero (check close to 0 in all dimensions)
     pub fn near_zero(&self) -> bool {
         let s = 1e-8;
@@ -206,6 +211,24 @@ pub fn random_on_hemisphere(normal: Vec3) -> Vec3 {
     }
 }
 
+/*
+ * Random direction on the hemisphere around +z, cosine weighted (pdf = cos(theta) / pi)
+ *
+ * Uniformly picks a point on the unit disk and projects it up onto the hemisphere (Malley's method).
+ * Use an Onb to orient the hemisphere around a surface normal.
+ */
+pub fn random_cosine_direction() -> Vec3 {
+    let r1 = random_double();
+    let r2 = random_double();
+
+    let phi = 2.0 * PI * r1;
+    let x = f64::cos(phi) * r2.sqrt();
+    let y = f64::sin(phi) * r2.sqrt();
+    let z = f64::sqrt(1.0 - r2);
+
+    Vec3::new(x, y, z)
+}
+
 /*
  * Used for defocus disk blur.
  */
@@ -0,0 +1,83 @@
+/*
+ * Worley (cellular / Voronoi) noise
+ *
+ * Space is split into unit cells and each cell gets one random feature point. For a point p we look at the
+ * feature points in the surrounding 3x3x3 cells and report the distance to the closest one (F1) and the
+ * second closest one (F2), as well as the cell that owns the closest point.
+ *
+ * Feature points come from hashing the cell coordinates with a random seed, so the pattern is infinite
+ * without storing anything per cell.
+ */
+
+use super::{utils::random_double, vec3::{Point3, Vec3}};
+
+pub struct WorleySample {
+    pub f1: f64, // Distance to the closest feature point
+    pub f2: f64, // Distance to the second closest feature point
+    pub cell_id: u64, // Hash identifying the cell owning the closest feature point
+}
+
+pub struct Worley {
+    seed: u64,
+}
+
+impl Default for Worley {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Worley {
+    pub fn new() -> Self {
+        Self { seed: (random_double() * u32::MAX as f64) as u64 }
+    }
+
+    pub fn sample(&self, p: &Point3) -> WorleySample {
+        let cell = (p.x().floor() as i64, p.y().floor() as i64, p.z().floor() as i64);
+        let mut f1 = f64::INFINITY;
+        let mut f2 = f64::INFINITY;
+        let mut cell_id = 0;
+
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let (i, j, k) = (cell.0 + di, cell.1 + dj, cell.2 + dk);
+                    let id = self.hash(i, j, k);
+                    let feature = Point3::new(i as f64, j as f64, k as f64) + Self::offset(id);
+                    let dist = (feature - *p).length();
+
+                    if dist < f1 {
+                        f2 = f1;
+                        f1 = dist;
+                        cell_id = id;
+                    } else if dist < f2 {
+                        f2 = dist;
+                    }
+                }
+            }
+        }
+
+        WorleySample { f1, f2, cell_id }
+    }
+
+    // Random-looking but deterministic 64-bit hash of a cell (SplitMix64 finalizer)
+    fn hash(&self, i: i64, j: i64, k: i64) -> u64 {
+        let mut h = self.seed
+            ^ (i as u64).wrapping_mul(0x9E3779B97F4A7C15)
+            ^ (j as u64).wrapping_mul(0xC2B2AE3D27D4EB4F)
+            ^ (k as u64).wrapping_mul(0x165667B19E3779F9);
+        h = (h ^ (h >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94D049BB133111EB);
+        h ^ (h >> 31)
+    }
+
+    // Position of the feature point inside its cell, in [0, 1)^3
+    fn offset(id: u64) -> Vec3 {
+        Vec3::new(hash_to_unit(id), hash_to_unit(id >> 21), hash_to_unit(id >> 42))
+    }
+}
+
+// Maps the low 21 bits of a hash to [0, 1)
+pub fn hash_to_unit(h: u64) -> f64 {
+    (h & 0x1FFFFF) as f64 / (1u64 << 21) as f64
+}